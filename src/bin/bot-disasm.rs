@@ -0,0 +1,30 @@
+use std::path::PathBuf;
+
+use shitty_bot_game::bot::{disasm, program};
+
+const USAGE: &str = "\
+usage: bot-disasm <program>
+
+Prints <program> as assembler source, marking each memory cell as code,
+operand, or unreachable depending on the control flow starting at cell 0.
+Programs ending in `.asm` are assembled, `.bin` files are raw memory images,
+and all other files are read as `.bot` files.";
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let path = match &*args {
+        [path] if path != "-h" && path != "--help" => PathBuf::from(path),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    match program::load(&path) {
+        Ok(instructions) => print!("{}", disasm::disassemble(&instructions)),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use shitty_bot_game::bot::program;
+use shitty_bot_game::bot::sim::{Outcome, Simulation};
+use shitty_bot_game::level_loader;
+
+const USAGE: &str = "\
+usage: bot-run [--assets <dir>] [--max-ticks <n>] <level> <program>...
+
+Runs one program per bot of <level> until the level is won, failed, or
+<n> ticks have passed. <level> is either the index of the level in
+`levels.json` or its name. Programs ending in `.asm` are
+assembled, `.bin` files are raw memory images, and all other files are
+read as `.bot` files.
+
+exit status: 0 if the level was won, 1 if it failed or timed out, 2 on error";
+
+struct Args {
+    assets: PathBuf,
+    max_ticks: u64,
+    level: String,
+    programs: Vec<PathBuf>,
+}
+
+fn parse_args() -> anyhow::Result<Args> {
+    let mut assets = PathBuf::from("assets");
+    let mut max_ticks = 10_000;
+    let mut positional = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &*arg {
+            "--assets" => assets = args.next().context("missing value for `--assets`")?.into(),
+            "--max-ticks" => {
+                let value = args.next().context("missing value for `--max-ticks`")?;
+                max_ticks = value
+                    .parse()
+                    .with_context(|| format!("invalid tick limit `{}`", value))?;
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    if positional.len() < 2 {
+        bail!("expected a level and at least one program");
+    }
+    let level = positional.remove(0);
+    Ok(Args {
+        assets,
+        max_ticks,
+        level,
+        programs: positional.into_iter().map(PathBuf::from).collect(),
+    })
+}
+
+fn run() -> anyhow::Result<bool> {
+    let args = parse_args()?;
+    let levels = level_loader::load_levels(&args.assets)?;
+    let level = match args.level.parse::<usize>() {
+        Ok(i) => levels.get(i),
+        Err(_) => levels.iter().find(|level| level.name == args.level),
+    }
+    .with_context(|| format!("unknown level `{}`", args.level))?;
+
+    if args.programs.len() != level.bots.len() {
+        bail!(
+            "level `{}` has {} bot(s) but {} program(s) were given",
+            level.name,
+            level.bots.len(),
+            args.programs.len()
+        );
+    }
+    let programs = args
+        .programs
+        .iter()
+        .map(|path| program::load(path).map_err(anyhow::Error::msg))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    for (path, program) in args.programs.iter().zip(&programs) {
+        program::check_memory(level, program)
+            .map_err(|e| anyhow::anyhow!("invalid program {:?}, {}", path, e))?;
+    }
+
+    let mut sim = Simulation::from_level(level, &programs);
+    while sim.tick < args.max_ticks {
+        sim.step();
+        match sim.outcome() {
+            Some(Outcome::Won) => {
+                match level.meta.par {
+                    Some(par) => println!("won after {} ticks (par {})", sim.tick, par),
+                    None => println!("won after {} ticks", sim.tick),
+                }
+                return Ok(true);
+            }
+            Some(Outcome::Failed(failure)) => {
+                println!("failed after {} ticks: {}", sim.tick, failure.0);
+                return Ok(false);
+            }
+            None => {}
+        }
+    }
+
+    println!("timeout: no outcome after {} ticks", sim.tick);
+    Ok(false)
+}
+
+fn main() {
+    match run() {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    }
+}
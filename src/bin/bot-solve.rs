@@ -0,0 +1,115 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use shitty_bot_game::bot::disasm;
+use shitty_bot_game::level_loader;
+use shitty_bot_game::solver::Solver;
+
+const USAGE: &str = "\
+usage: bot-solve [--assets <dir>] [--max-cells <n>] [--max-ticks <n>] [<level>...]
+
+Searches for the shortest program solving each <level>, or all levels in
+`levels.json` if none are given. All bots of a level run the same program.
+<level> is either the index of the level in `levels.json` or its name.
+
+exit status: 0 if every level has a solution, 1 if some don't, 2 on error";
+
+struct Args {
+    assets: PathBuf,
+    max_cells: usize,
+    max_ticks: u64,
+    levels: Vec<String>,
+}
+
+fn parse_args() -> anyhow::Result<Args> {
+    let mut assets = PathBuf::from("assets");
+    let mut max_cells = 6;
+    let mut max_ticks = 200;
+    let mut levels = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &*arg {
+            "--assets" => assets = args.next().context("missing value for `--assets`")?.into(),
+            "--max-cells" => {
+                let value = args.next().context("missing value for `--max-cells`")?;
+                max_cells = value
+                    .parse()
+                    .with_context(|| format!("invalid cell limit `{}`", value))?;
+            }
+            "--max-ticks" => {
+                let value = args.next().context("missing value for `--max-ticks`")?;
+                max_ticks = value
+                    .parse()
+                    .with_context(|| format!("invalid tick limit `{}`", value))?;
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            _ if arg.starts_with("--") => bail!("unknown option `{}`", arg),
+            _ => levels.push(arg),
+        }
+    }
+
+    Ok(Args {
+        assets,
+        max_cells,
+        max_ticks,
+        levels,
+    })
+}
+
+fn run() -> anyhow::Result<bool> {
+    let args = parse_args()?;
+    let levels = level_loader::load_levels(&args.assets)?;
+    let selected = if args.levels.is_empty() {
+        (0..levels.len()).collect()
+    } else {
+        args.levels
+            .iter()
+            .map(|name| match name.parse::<usize>() {
+                Ok(i) if i < levels.len() => Ok(i),
+                _ => levels
+                    .iter()
+                    .position(|level| level.name == *name)
+                    .with_context(|| format!("unknown level `{}`", name)),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?
+    };
+
+    let mut all_solved = true;
+    for i in selected {
+        let level = &levels[i];
+        let mut solver = Solver::new(level, args.max_ticks);
+        match solver.solve(args.max_cells) {
+            Some(solution) => {
+                println!(
+                    "{} ({}): solved with {} cells in {} ticks, {} prefixes explored",
+                    i, level.name, solution.cells, solution.ticks, solver.explored
+                );
+                print!("{}", disasm::disassemble(&solution.instructions));
+            }
+            None => {
+                all_solved = false;
+                println!(
+                    "{} ({}): no solution with at most {} cells, {} prefixes explored",
+                    i, level.name, args.max_cells, solver.explored
+                );
+            }
+        }
+    }
+
+    Ok(all_solved)
+}
+
+fn main() {
+    match run() {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    }
+}
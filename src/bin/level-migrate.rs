@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+
+use shitty_bot_game::level_loader;
+
+const USAGE: &str = "\
+usage: level-migrate [<assets>]
+
+Converts every level in <assets>/levels.json which still uses a separate
+`.map` file into a single `.level` file and removes the `.map` file.
+<assets> defaults to `assets`.";
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let assets = match &*args {
+        [] => PathBuf::from("assets"),
+        [path] if path != "-h" && path != "--help" => PathBuf::from(path),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    match level_loader::migrate_levels(&assets) {
+        Ok(migrated) if migrated.is_empty() => println!("all levels are up to date"),
+        Ok(migrated) => {
+            for name in migrated {
+                println!("migrated `{}`", name);
+            }
+        }
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            std::process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use crate::bot::program::{self, ProgramError};
+
+enum Operand<'a> {
+    Value(u8),
+    Label(&'a str),
+}
+
+struct Cell<'a> {
+    line: usize,
+    operand: Operand<'a>,
+}
+
+fn is_label(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn parse_operand(word: &str) -> Result<Operand<'_>, String> {
+    if word.starts_with(|c: char| c.is_ascii_digit()) {
+        program::parse_value(word).map(Operand::Value)
+    } else if is_label(word) {
+        Ok(Operand::Label(word))
+    } else {
+        Err(format!("expected a value or a label, found `{}`", word))
+    }
+}
+
+/// Assembles a program with symbolic labels into a memory image.
+///
+/// Each line contains an optional `label:` followed by either an instruction
+/// with its operands, e.g. `goto loop`, `walk 2` or `store count 3`, or a single value.
+/// Everything after a `;` is a comment.
+pub fn assemble(src: &str) -> Result<[u8; 32], ProgramError> {
+    let mut labels = HashMap::new();
+    let mut cells = Vec::new();
+    for (i, line) in src.lines().enumerate() {
+        let line_nr = i + 1;
+        let err = |msg| ProgramError { line: line_nr, msg };
+        let mut line = line.split(';').next().unwrap().trim();
+
+        if let Some((label, rest)) = line.split_once(':') {
+            let label = label.trim();
+            if !is_label(label) {
+                return Err(err(format!("invalid label `{}`", label)));
+            }
+            if let Some(prev) = labels.insert(label, (cells.len(), line_nr)) {
+                return Err(err(format!(
+                    "the label `{}` is already defined on line {}",
+                    label, prev.1
+                )));
+            }
+            line = rest.trim();
+        }
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if line.starts_with(|c: char| c.is_ascii_digit()) {
+            let value = program::parse_value(line).map_err(err)?;
+            cells.push(Cell {
+                line: line_nr,
+                operand: Operand::Value(value),
+            });
+            continue;
+        }
+
+        let mut words = line.split_whitespace();
+        let instr = program::parse_instruction_words(&mut words).map_err(err)?;
+        cells.push(Cell {
+            line: line_nr,
+            operand: Operand::Value(instr.repr()),
+        });
+        for name in instr.operands() {
+            let word = words
+                .next()
+                .ok_or_else(|| err(format!("missing operand `{}` for `{}`", name, instr)))?;
+            cells.push(Cell {
+                line: line_nr,
+                operand: parse_operand(word).map_err(err)?,
+            });
+        }
+        if let Some(word) = words.next() {
+            return Err(err(format!(
+                "unexpected word `{}` after the end of the instruction",
+                word
+            )));
+        }
+    }
+
+    let mut instructions = [0; 32];
+    if let Some(cell) = cells.get(instructions.len()) {
+        return Err(ProgramError {
+            line: cell.line,
+            msg: format!(
+                "the program does not fit into the {} available memory cells",
+                instructions.len()
+            ),
+        });
+    }
+
+    for (value, cell) in instructions.iter_mut().zip(&cells) {
+        *value = match cell.operand {
+            Operand::Value(v) => v,
+            Operand::Label(label) => match labels.get(label) {
+                Some(&(addr, _)) if addr < 32 => addr as u8,
+                Some(&(_, line)) => {
+                    return Err(ProgramError {
+                        line,
+                        msg: format!("the label `{}` points past the end of memory", label),
+                    })
+                }
+                None => {
+                    return Err(ProgramError {
+                        line: cell.line,
+                        msg: format!("unknown label `{}`", label),
+                    })
+                }
+            },
+        };
+    }
+
+    Ok(instructions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::Instruction;
+
+    fn error(src: &str) -> ProgramError {
+        match assemble(src) {
+            Ok(_) => panic!("expected `{}` to fail", src),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn labels() {
+        let program = assemble("start: walk 1\n  if wall end\n  goto start\nend: 0").unwrap();
+        let goto = Instruction::Goto.repr();
+        let if_wall = Instruction::IfWall.repr();
+        assert_eq!(
+            program[..7],
+            [Instruction::Walk.repr(), 1, if_wall, 6, goto, 0, 0]
+        );
+    }
+
+    #[test]
+    fn unknown_label() {
+        let e = error("walk 1\ngoto nowhere");
+        assert_eq!(e.line, 2);
+        assert_eq!(e.msg, "unknown label `nowhere`");
+    }
+
+    #[test]
+    fn duplicate_label() {
+        let e = error("a: walk 1\na: goto a");
+        assert_eq!(e.line, 2);
+        assert_eq!(e.msg, "the label `a` is already defined on line 1");
+    }
+
+    #[test]
+    fn invalid_label() {
+        let e = error("walk 1\n1a: goto 1a");
+        assert_eq!(e.line, 2);
+        assert_eq!(e.msg, "invalid label `1a`");
+    }
+
+    #[test]
+    fn label_past_end_of_memory() {
+        let src = "goto end\n".repeat(16) + "end:";
+        let e = error(&src);
+        assert_eq!(e.line, 17);
+        assert_eq!(e.msg, "the label `end` points past the end of memory");
+    }
+}
@@ -0,0 +1,114 @@
+use crate::bot::sim::Simulation;
+
+/// A request to progress a paused simulation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Advance {
+    Tick,
+    /// Progress until the given bot is about to execute its next instruction.
+    Instruction {
+        bot: usize,
+        stepped: bool,
+    },
+}
+
+/// Controls whether `progress_world` may step the simulation.
+#[derive(Debug, Clone)]
+pub struct Debugger {
+    pub paused: bool,
+    pub advance: Option<Advance>,
+    /// The breakpoints of each bot, stopping the simulation
+    /// right before the bot executes the instruction in that cell.
+    pub breakpoints: Vec<[bool; 32]>,
+    /// Set when resuming so that the simulation doesn't
+    /// immediately stop at the breakpoint it is paused at.
+    skip_breakpoint: bool,
+}
+
+impl Debugger {
+    pub fn new(bots: usize) -> Self {
+        Debugger {
+            paused: false,
+            advance: None,
+            breakpoints: vec![[false; 32]; bots],
+            skip_breakpoint: false,
+        }
+    }
+
+    /// Resets everything but the breakpoints for a new run.
+    pub fn restart(&mut self) {
+        self.paused = false;
+        self.advance = None;
+        self.skip_breakpoint = false;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.advance = None;
+        self.skip_breakpoint = !self.paused;
+    }
+
+    /// Pauses the simulation after the next tick.
+    pub fn step_tick(&mut self) {
+        self.paused = true;
+        self.advance = Some(Advance::Tick);
+    }
+
+    /// Pauses the simulation once `bot` is about to execute another instruction.
+    pub fn step_instruction(&mut self, bot: usize) {
+        self.paused = true;
+        self.advance = Some(Advance::Instruction {
+            bot,
+            stepped: false,
+        });
+    }
+
+    pub fn toggle_breakpoint(&mut self, bot: usize, cell: usize) {
+        if let Some(breakpoints) = self.breakpoints.get_mut(bot) {
+            breakpoints[cell] = !breakpoints[cell];
+        }
+    }
+
+    pub fn is_breakpoint(&self, bot: usize, cell: usize) -> bool {
+        self.breakpoints
+            .get(bot)
+            .map_or(false, |b| b.get(cell) == Some(&true))
+    }
+
+    fn at_breakpoint(&self, sim: &Simulation) -> bool {
+        sim.bots.iter().enumerate().any(|(i, bot)| {
+            bot.state
+                .next_instruction()
+                .map_or(false, |cell| self.is_breakpoint(i, cell as usize))
+        })
+    }
+
+    /// Whether the simulation should be stepped once, pausing it
+    /// if it reached a breakpoint.
+    pub fn should_step(&mut self, sim: &Simulation) -> bool {
+        if sim.outcome().is_some() {
+            self.advance = None;
+        }
+
+        match self.advance.take() {
+            Some(Advance::Tick) => true,
+            Some(Advance::Instruction { bot, stepped }) => {
+                let reached = sim.bots[bot].state.next_instruction().is_some();
+                if stepped && (reached || self.at_breakpoint(sim)) {
+                    false
+                } else {
+                    self.advance = Some(Advance::Instruction { bot, stepped: true });
+                    true
+                }
+            }
+            None if self.paused => false,
+            None if self.at_breakpoint(sim) && !self.skip_breakpoint => {
+                self.paused = true;
+                false
+            }
+            None => {
+                self.skip_breakpoint = false;
+                true
+            }
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::fmt::{self, Display, Write};
+
+use crate::bot::Instruction;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellKind {
+    Unreachable,
+    Code,
+    Operand,
+    /// The cell is executed by some path and used as an operand by another.
+    CodeAndOperand,
+}
+
+impl Display for CellKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CellKind::Unreachable => "unreachable",
+            CellKind::Code => "code",
+            CellKind::Operand => "operand",
+            CellKind::CodeAndOperand => "code and operand",
+        })
+    }
+}
+
+pub struct Analysis {
+    pub kinds: [CellKind; 32],
+    pub jump_targets: [bool; 32],
+    /// The cells written by `store`, `inc` or `dec`.
+    pub written: [bool; 32],
+}
+
+/// Classifies each cell by following all control flow reachable from cell 0.
+///
+/// The analysis uses the initial memory, so jumps and instructions
+/// which only exist after the program changed itself are missed.
+pub fn analyse(instructions: &[u8; 32]) -> Analysis {
+    let mut code = [false; 32];
+    let mut operand = [false; 32];
+    let mut jump_targets = [false; 32];
+    let mut written = [false; 32];
+
+    let mut todo = vec![0];
+    while let Some(cell) = todo.pop() {
+        if cell >= code.len() || code[cell] {
+            continue;
+        }
+        code[cell] = true;
+
+        let next = |offset| (cell + offset) % 32;
+        let instr = match Instruction::from_repr(instructions[cell]) {
+            Some(instr) => instr,
+            None => continue,
+        };
+        let len = instr.operands().len();
+        for i in 1..=len {
+            operand[next(i)] = true;
+        }
+
+        let target = instructions[next(1)] as usize;
+        let mut jump_to = |target: usize| {
+            if let Some(t) = jump_targets.get_mut(target) {
+                *t = true;
+            }
+            todo.push(target);
+        };
+        match instr {
+            Instruction::Halt => {}
+            Instruction::Walk => todo.push(next(2)),
+            Instruction::TurnAround
+            | Instruction::TurnLeft
+            | Instruction::TurnRight
+            | Instruction::Skip => todo.push(next(1)),
+            Instruction::Goto => jump_to(target),
+            Instruction::IfBox
+            | Instruction::IfWall
+            | Instruction::IfEdge
+            | Instruction::IfNotBox
+            | Instruction::IfNotWall
+            | Instruction::IfNotEdge
+            | Instruction::IfBot
+            | Instruction::IfNotBot => {
+                jump_to(target);
+                todo.push(next(2));
+            }
+            Instruction::Store | Instruction::Inc | Instruction::Dec => {
+                if let Some(w) = written.get_mut(target) {
+                    *w = true;
+                }
+                todo.push(next(1 + len));
+            }
+        }
+    }
+
+    let kinds = std::array::from_fn(|i| match (code[i], operand[i]) {
+        (false, false) => CellKind::Unreachable,
+        (true, false) => CellKind::Code,
+        (false, true) => CellKind::Operand,
+        (true, true) => CellKind::CodeAndOperand,
+    });
+    Analysis {
+        kinds,
+        jump_targets,
+        written,
+    }
+}
+
+fn label(cell: usize) -> String {
+    format!("l{}", cell)
+}
+
+/// Renders a memory image as source for `asm::assemble`, annotating each line
+/// with the cells it occupies and how they are reached.
+///
+/// Instructions are only combined with their operands if no operand cell is
+/// executed or targeted by a jump or write, all other cells are emitted as values.
+pub fn disassemble(instructions: &[u8; 32]) -> String {
+    let Analysis {
+        kinds,
+        jump_targets,
+        written,
+    } = analyse(instructions);
+    let labeled = |cell: usize| jump_targets[cell] || written[cell];
+
+    // unreachable `halt`s at the end of the memory are omitted, unless they are written to.
+    let len = (0..32)
+        .rposition(|i| instructions[i] != 0 || kinds[i] != CellKind::Unreachable || labeled(i))
+        .map_or(0, |i| i + 1);
+
+    let mut out = String::new();
+    let mut cell = 0;
+    while cell < len {
+        if labeled(cell) {
+            writeln!(out, "{}:", label(cell)).unwrap();
+        }
+
+        let value = instructions[cell];
+        let kind = kinds[cell];
+        let (text, size, note) = match Instruction::from_repr(value) {
+            Some(instr) if kind == CellKind::Code && !instr.is_wide() => {
+                (instr.to_string(), 1, String::new())
+            }
+            Some(instr)
+                if kind == CellKind::Code
+                    && cell + instr.operands().len() < 32
+                    && (1..=instr.operands().len())
+                        .all(|i| kinds[cell + i] == CellKind::Operand && !labeled(cell + i)) =>
+            {
+                let mut text = instr.to_string();
+                for i in 1..=instr.operands().len() {
+                    let arg = instructions[cell + i];
+                    // only the first operand can be an address.
+                    if i == 1 && instr.is_address() && arg < 32 {
+                        text += &format!(" {}", label(arg as usize));
+                    } else {
+                        text += &format!(" {}", arg);
+                    }
+                }
+                (text, 1 + instr.operands().len(), String::new())
+            }
+            Some(instr) if kind != CellKind::Operand => {
+                (value.to_string(), 1, format!(" (`{}`)", instr))
+            }
+            _ => (value.to_string(), 1, String::new()),
+        };
+
+        let cells = if size > 1 {
+            format!("{}-{}", cell, cell + size - 1)
+        } else {
+            cell.to_string()
+        };
+        writeln!(out, "    {:<20} ; {:<6} {}{}", text, cells, kind, note).unwrap();
+        cell += size;
+    }
+
+    if len < 32 {
+        let cells = format!("{}-31", len);
+        writeln!(out, "    {:<20} ; {:<6} unreachable halt", "", cells).unwrap();
+    }
+    out
+}
@@ -1,25 +1,224 @@
+use crate::bot::program;
 use crate::bot::Instruction;
 use std::array;
+use std::ops::{Deref, DerefMut};
+
+/// The program of each bot, dereferencing to the one currently being edited.
+pub struct ProgramEditors {
+    pub editors: Vec<InstructionsEditor>,
+    pub active: usize,
+}
+
+impl ProgramEditors {
+    pub fn new(bots: usize) -> Self {
+        ProgramEditors {
+            editors: (0..bots.max(1))
+                .map(|_| InstructionsEditor::new())
+                .collect(),
+            active: 0,
+        }
+    }
+
+    /// Restores previously written programs, starting
+    /// from an empty program for any bot without one.
+    pub fn from_programs(bots: usize, programs: &[[u8; 32]]) -> Self {
+        let mut editors = ProgramEditors::new(bots);
+        for (editor, &program) in editors.editors.iter_mut().zip(programs) {
+            *editor = InstructionsEditor::from_instructions(program);
+        }
+        editors
+    }
+}
+
+impl Deref for ProgramEditors {
+    type Target = InstructionsEditor;
+    fn deref(&self) -> &InstructionsEditor {
+        &self.editors[self.active]
+    }
+}
+
+impl DerefMut for ProgramEditors {
+    fn deref_mut(&mut self) -> &mut InstructionsEditor {
+        &mut self.editors[self.active]
+    }
+}
+
+fn instruction_name(value: u8) -> String {
+    Instruction::from_repr(value).map_or(String::new(), |i| i.to_string())
+}
+
 pub struct InstructionsEditor {
     pub user_names: [String; 32],
     pub user_values: [String; 32],
     pub instructions: [u8; 32],
     pub active_cell: Option<(bool, usize)>,
     pub error: Option<String>,
+    /// The contents of `instructions` before each edit.
+    undo: Vec<[u8; 32]>,
+    redo: Vec<[u8; 32]>,
 }
 
 impl InstructionsEditor {
     pub fn new() -> Self {
-        let instructions = array::from_fn(|_| Instruction::Halt.repr());
+        InstructionsEditor::from_instructions(array::from_fn(|_| Instruction::Halt.repr()))
+    }
+
+    pub fn from_instructions(instructions: [u8; 32]) -> Self {
         InstructionsEditor {
-            user_names: instructions.map(|i| format!("{}", Instruction::from_repr(i).unwrap())),
+            user_names: instructions
+                .map(|i| Instruction::from_repr(i).map_or(String::new(), |i| format!("{}", i))),
             user_values: instructions.map(|i| format!("{}", i)),
             instructions,
             active_cell: None,
             error: None,
+            undo: Vec::new(),
+            redo: Vec::new(),
         }
     }
 
+    /// Replaces the whole program as a single edit which can be undone.
+    pub fn replace(&mut self, instructions: [u8; 32]) {
+        self.record();
+        self.set_instructions(instructions);
+    }
+
+    /// Reverts the last edit, returning whether there was anything to revert.
+    ///
+    /// An invalid edit never changed `instructions`,
+    /// so undoing it only discards the user input.
+    pub fn undo(&mut self) -> bool {
+        if self.error.is_some() {
+            self.set_instructions(self.instructions);
+            return true;
+        }
+
+        match self.undo.pop() {
+            Some(instructions) => {
+                self.redo.push(self.instructions);
+                self.set_instructions(instructions);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self) -> bool {
+        match self.redo.pop() {
+            Some(instructions) => {
+                self.undo.push(self.instructions);
+                self.set_instructions(instructions);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Inserts a `halt` at `cell`, moving all later cells back by one.
+    /// If `cell` is an operand, the `halt` is inserted before its instruction instead.
+    ///
+    /// Jumps to and writes of moved cells are updated. Returns a warning if a
+    /// cell or the target of an address is moved past the end of the memory.
+    pub fn insert_row(&mut self, cell: usize) -> Option<String> {
+        let old = self.instructions;
+        let operands = program::operand_cells(&old);
+        let cell = (0..=cell).rev().find(|&c| !operands[c]).unwrap_or(0);
+        let mut new = [Instruction::Halt.repr(); 32];
+        new[..cell].copy_from_slice(&old[..cell]);
+        new[cell + 1..].copy_from_slice(&old[cell..31]);
+
+        let mut warnings = Vec::new();
+        if old[31] != Instruction::Halt.repr() || program::operand_cells(&old)[31] {
+            warnings.push(format!(
+                "the value `{}` in cell 31 was moved past the end of the memory and lost",
+                old[31]
+            ));
+        }
+        for operand in program::address_operands(&old) {
+            let target = old[operand] as usize;
+            let new_operand = if operand >= cell {
+                operand + 1
+            } else {
+                operand
+            };
+            if target < cell || new_operand >= new.len() {
+                continue;
+            }
+            if target + 1 < new.len() {
+                new[new_operand] = target as u8 + 1;
+            } else {
+                warnings.push(format!(
+                    "the `{}` in cell {} now targets cell 31, \
+                    as its target was moved past the end of the memory",
+                    instruction_name(old[operand - 1]),
+                    new_operand - 1
+                ));
+            }
+        }
+
+        self.replace(new);
+        (!warnings.is_empty()).then(|| warnings.join("; "))
+    }
+
+    /// Removes `cell`, moving all later cells forward by one and filling
+    /// the last cell with `halt`.
+    ///
+    /// Jumps to and writes of moved cells are updated. Returns a warning
+    /// if a jump or write targeted the removed cell. Operands can't be
+    /// removed on their own, so nothing changes if `cell` is one.
+    pub fn delete_row(&mut self, cell: usize) -> Option<String> {
+        let old = self.instructions;
+        let operands = program::operand_cells(&old);
+        if operands[cell] {
+            let instr = (0..cell).rev().find(|&c| !operands[c]).unwrap_or(0);
+            return Some(format!(
+                "cell {} is an operand of the `{}` in cell {} and can't be removed on its own",
+                cell,
+                instruction_name(old[instr]),
+                instr
+            ));
+        }
+        let mut new = [Instruction::Halt.repr(); 32];
+        new[..cell].copy_from_slice(&old[..cell]);
+        new[cell..31].copy_from_slice(&old[cell + 1..]);
+
+        let mut warnings = Vec::new();
+        for operand in program::address_operands(&old) {
+            let target = old[operand] as usize;
+            // the instruction itself was removed.
+            if operand == cell || operand - 1 == cell {
+                continue;
+            }
+            let new_operand = if operand > cell { operand - 1 } else { operand };
+            if target == cell {
+                warnings.push(format!(
+                    "the `{}` in cell {} targeted the removed cell and now targets the cell after it",
+                    instruction_name(old[operand - 1]),
+                    new_operand - 1
+                ));
+            } else if target > cell {
+                new[new_operand] = target as u8 - 1;
+            }
+        }
+
+        self.replace(new);
+        (!warnings.is_empty()).then(|| warnings.join("; "))
+    }
+
+    fn record(&mut self) {
+        self.undo.push(self.instructions);
+        self.redo.clear();
+    }
+
+    /// Sets `instructions`, dropping the selection and any user input.
+    fn set_instructions(&mut self, instructions: [u8; 32]) {
+        self.instructions = instructions;
+        self.user_names = instructions
+            .map(|i| Instruction::from_repr(i).map_or(String::new(), |i| format!("{}", i)));
+        self.user_values = instructions.map(|i| format!("{}", i));
+        self.active_cell = None;
+        self.error = None;
+    }
+
     pub fn active_cell_data(&mut self) -> Option<&mut String> {
         self.active_cell.map(|(b, i)| {
             if b {
@@ -36,122 +235,36 @@ impl InstructionsEditor {
         // updating the `instructions` and other part of `user_x`.
         if was_name {
             self.user_names[cell] = self.user_names[cell].trim().to_string();
-            if self.user_names[cell] == "" {
+            if self.user_names[cell].is_empty() {
                 self.user_names[cell] = Instruction::from_repr(self.instructions[cell])
                     .map_or(String::new(), |i| format!("{}", i));
                 return;
             }
 
-            let mut words = self.user_names[cell].split_whitespace();
-            let instr = match words.next() {
-                Some("halt") => Instruction::Halt,
-                Some("walk") => Instruction::Walk,
-                Some("turn") => match words.next() {
-                    Some("around") => Instruction::TurnAround,
-                    Some("left") => Instruction::TurnLeft,
-                    Some("right") => Instruction::TurnRight,
-                    _ => {
-                        self.error = Some(format!(
-                            "invalid `turn` command, \
-                            expected one of `turn around`, `turn right`, or \
-                            `turn left`. found `{}`",
-                            self.user_names[cell]
-                        ));
-                        return;
-                    }
-                },
-                Some("skip") => Instruction::Skip,
-                Some("goto") => Instruction::Goto,
-                Some("if") => {
-                    let mut next = words.next();
-                    let negate = next == Some("not");
-                    if negate {
-                        next = words.next();
-                    }
-
-                    const BRANCH_COND_EXP: &str = "expected one of `box`, `wall`, or `edge`";
-                    match next {
-                        Some("box") => {
-                            if negate {
-                                Instruction::IfNotBox
-                            } else {
-                                Instruction::IfBox
-                            }
-                        }
-                        Some("wall") => {
-                            if negate {
-                                Instruction::IfNotWall
-                            } else {
-                                Instruction::IfWall
-                            }
-                        }
-                        Some("edge") => {
-                            if negate {
-                                Instruction::IfNotEdge
-                            } else {
-                                Instruction::IfEdge
-                            }
-                        }
-                        Some(e) => {
-                            self.error = Some(format!(
-                                "invalid branch condition, {}, found `{}`",
-                                BRANCH_COND_EXP, e
-                            ));
-                            return;
-                        }
-                        None => {
-                            self.error =
-                                Some(format!("missing branch condition, {}", BRANCH_COND_EXP));
-                            return;
-                        }
-                    }
-                }
-                _ => {
-                    self.error = Some(String::from(
-                        "invalid start of command, \
-                        expected one of `halt`, `walk`, `turn`, `skip`, `goto`, \
-                        or `if`. For more info about the available instructions, \
-                        refer to the manual.",
-                    ));
+            let instr = match program::parse_instruction(&self.user_names[cell]) {
+                Ok(instr) => instr,
+                Err(e) => {
+                    self.error = Some(e);
                     return;
                 }
             };
 
-            if let Some(s) = words.next() {
-                self.error = Some(format!(
-                    "unexpected word `{}`, `{}` is already a complete instruction",
-                    s, instr
-                ));
-                return;
-            }
-
-            self.instructions[cell] = instr.repr();
+            self.set_cell(cell, instr.repr());
         } else {
             self.user_values[cell] = self.user_values[cell].trim().to_string();
-            if self.user_values[cell] == "" {
+            if self.user_values[cell].is_empty() {
                 self.user_values[cell] = format!("{}", self.instructions[cell]);
                 return;
             }
-            let value = match self.user_values[cell].parse::<u128>() {
-                Ok(v) => {
-                    if v < 32 {
-                        v as u8
-                    } else {
-                        self.error = Some(format!(
-                            "the value `{}` cannot be stored as it is larger than 31",
-                            v
-                        ));
-                        return;
-                    }
-                }
-                // TODO: improve error msg.
+            let value = match program::parse_value(&self.user_values[cell]) {
+                Ok(v) => v,
                 Err(e) => {
-                    self.error = Some(format!("{}", e));
+                    self.error = Some(e);
                     return;
                 }
             };
 
-            self.instructions[cell] = value;
+            self.set_cell(cell, value);
         }
 
         self.user_names[cell] = Instruction::from_repr(self.instructions[cell])
@@ -159,6 +272,13 @@ impl InstructionsEditor {
         self.user_values[cell] = self.instructions[cell].to_string();
     }
 
+    fn set_cell(&mut self, cell: usize, value: u8) {
+        if self.instructions[cell] != value {
+            self.record();
+            self.instructions[cell] = value;
+        }
+    }
+
     pub fn on_selection_quit(&mut self, new_cell: Option<(bool, usize)>) -> Option<(bool, usize)> {
         let old = self.active_cell.take();
         self.active_cell = new_cell;
@@ -167,3 +287,79 @@ impl InstructionsEditor {
         old
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::asm;
+
+    fn editor(src: &str) -> InstructionsEditor {
+        InstructionsEditor::from_instructions(asm::assemble(src).unwrap())
+    }
+
+    #[test]
+    fn insert_row_moves_targets() {
+        let mut editor = editor("walk 1\nloop: turn left\nif wall loop\ngoto 0");
+        assert_eq!(editor.insert_row(2), None);
+        let expected = asm::assemble("walk 1\nhalt\nloop: turn left\nif wall loop\ngoto 0");
+        assert_eq!(editor.instructions, expected.unwrap());
+    }
+
+    #[test]
+    fn insert_row_warns_about_lost_cells() {
+        let mut editor = editor(&"skip\n".repeat(30));
+        editor.instructions[31] = 5;
+        let warning = editor.insert_row(0).unwrap();
+        assert!(warning.contains("cell 31"), "{}", warning);
+        assert_eq!(editor.instructions[0], Instruction::Halt.repr());
+    }
+
+    #[test]
+    fn delete_row_moves_targets() {
+        let mut editor = editor("walk 1\nskip\nloop: turn left\nif wall loop\ngoto 0");
+        assert_eq!(editor.delete_row(2), None);
+        let expected = asm::assemble("walk 1\nloop: turn left\nif wall loop\ngoto 0");
+        assert_eq!(editor.instructions, expected.unwrap());
+    }
+
+    #[test]
+    fn delete_row_warns_about_removed_target() {
+        let mut editor = editor("goto end\nskip\nend: turn left");
+        let warning = editor.delete_row(3).unwrap();
+        assert!(warning.contains("targeted the removed cell"), "{}", warning);
+        assert_eq!(editor.instructions[..2], [Instruction::Goto.repr(), 3]);
+    }
+
+    #[test]
+    fn insert_row_at_operand() {
+        let mut editor = editor("turn left\nstore 5 2\nwalk 1");
+        assert_eq!(editor.insert_row(3), None);
+        let expected = asm::assemble("turn left\nhalt\nstore 6 2\nwalk 1");
+        assert_eq!(editor.instructions, expected.unwrap());
+    }
+
+    #[test]
+    fn delete_row_at_operand() {
+        let program = asm::assemble("turn left\nwalk 1\nskip").unwrap();
+        let mut editor = InstructionsEditor::from_instructions(program);
+        let warning = editor.delete_row(2).unwrap();
+        assert!(
+            warning.contains("operand of the `walk` in cell 1"),
+            "{}",
+            warning
+        );
+        assert_eq!(editor.instructions, program);
+    }
+
+    #[test]
+    fn insert_and_delete_row_round_trip() {
+        let program = asm::assemble("a: walk 2\nif not box a\nturn right\ngoto a").unwrap();
+        let mut editor = InstructionsEditor::from_instructions(program);
+        let operands = program::operand_cells(&program);
+        for cell in (0..8).filter(|&cell| !operands[cell]) {
+            editor.insert_row(cell);
+            editor.delete_row(cell);
+            assert_eq!(editor.instructions, program, "cell {}", cell);
+        }
+    }
+}
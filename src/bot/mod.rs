@@ -1,26 +1,39 @@
 use std::fmt::Display;
+use std::iter;
 
 use bevy::prelude::*;
 
-use crate::draw::{self, DrawUpdates};
+use crate::draw::{DrawUpdates, SimSpeed};
+use crate::replay::{Replay, ReplayKind};
+use crate::save::SaveGame;
+use crate::ui::{self, running::NextLevelButton, ErrorText};
 use crate::Direction;
 use crate::{map::*, CurrentLevel, GameState};
+use debugger::Debugger;
+use sim::{Outcome, SimEntity, Simulation};
+use timeline::Timeline;
 
+pub mod asm;
+pub mod debugger;
+pub mod disasm;
 pub mod edit;
-
-#[derive(Component, Copy, Clone, Debug)]
-pub struct VoidedOrExited;
+pub mod program;
+pub mod sim;
+pub mod timeline;
 
 #[derive(Component)]
 pub struct BotData {
+    /// The position of this bot in `Level::bots`.
+    pub index: usize,
     pub instructions: [u8; 32],
     pub start_position: GridPos,
     pub start_dir: Direction,
 }
 
 impl BotData {
-    pub fn new(pos: GridPos, dir: Direction) -> Self {
+    pub fn new(index: usize, pos: GridPos, dir: Direction) -> Self {
         BotData {
+            index,
             instructions: [0; 32],
             start_position: pos,
             start_dir: dir,
@@ -28,52 +41,77 @@ impl BotData {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Step {
     Wait,
     Walk,
     UpdateDir(Direction),
 }
 
-#[derive(Debug, Component)]
+#[derive(Debug, Clone)]
 pub struct BotState {
     halted: bool,
     pub prev_instruction: u8,
     current_instruction: u8,
     steps: Vec<Step>,
     pub dir: Direction,
+    /// The memory of the bot, starting out as its program and
+    /// changed by `store`, `inc` and `dec`.
+    pub memory: [u8; 32],
 }
 
 impl BotState {
-    pub fn new(dir: Direction) -> Self {
+    pub fn new(dir: Direction, program: [u8; 32]) -> Self {
         BotState {
             halted: false,
             prev_instruction: 0,
             current_instruction: 0,
             steps: Vec::new(),
             dir,
+            memory: program,
         }
     }
 
-    fn advance_instruction(&mut self) {
-        if self.current_instruction == 31 {
-            self.current_instruction = 0;
+    /// The cell of the instruction which is executed in the next tick,
+    /// if the bot is neither halted nor still busy with its previous instruction.
+    pub fn next_instruction(&self) -> Option<u8> {
+        if self.halted || !self.steps.is_empty() {
+            None
         } else {
-            self.current_instruction += 1;
+            Some(self.current_instruction)
         }
     }
 
-    fn read_instruction(&mut self, data: &BotData) -> Option<Instruction> {
-        let instr = Instruction::from_repr(data.instructions[self.current_instruction as usize]);
+    fn advance_instruction(&mut self) {
+        // a jump outside of the memory is kept, so that the bot halts.
+        if self.current_instruction < 32 {
+            self.current_instruction = (self.current_instruction + 1) % 32;
+        }
+    }
+
+    fn read_instruction(&mut self) -> Option<Instruction> {
+        // jumping outside of the memory halts the bot.
+        let instr = self
+            .memory
+            .get(self.current_instruction as usize)
+            .and_then(|&i| Instruction::from_repr(i));
         self.advance_instruction();
         instr
     }
 
-    fn read_value(&mut self, data: &BotData) -> u8 {
-        let value = data.instructions[self.current_instruction as usize];
+    fn read_value(&mut self) -> u8 {
+        let value = self.memory[self.current_instruction as usize];
         self.advance_instruction();
         value
     }
+
+    /// Applies `f` to the cell at `addr`, halting the bot if there is no such cell.
+    fn write_memory(&mut self, addr: u8, f: impl FnOnce(u8) -> u8) {
+        match self.memory.get_mut(addr as usize) {
+            Some(cell) => *cell = f(*cell),
+            None => self.halted = true,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -92,6 +130,11 @@ pub enum Instruction {
     IfNotBox,
     IfNotWall,
     IfNotEdge,
+    IfBot,
+    IfNotBot,
+    Store,
+    Inc,
+    Dec,
 }
 
 impl Display for Instruction {
@@ -113,27 +156,115 @@ impl Display for Instruction {
                 Instruction::IfNotBox => "if not box",
                 Instruction::IfNotWall => "if not wall",
                 Instruction::IfNotEdge => "if not edge",
+                Instruction::IfBot => "if bot",
+                Instruction::IfNotBot => "if not bot",
+                Instruction::Store => "store",
+                Instruction::Inc => "inc",
+                Instruction::Dec => "dec",
             }
         )
     }
 }
 
 impl Instruction {
-    pub fn is_wide(self) -> bool {
+    /// The names of the operands of this instruction, which are
+    /// stored in the cells directly after it.
+    pub fn operands(self) -> &'static [&'static str] {
         match self {
             Instruction::Halt
             | Instruction::Skip
             | Instruction::TurnAround
             | Instruction::TurnLeft
-            | Instruction::TurnRight => false,
-            Instruction::Walk
-            | Instruction::Goto
+            | Instruction::TurnRight => &[],
+            Instruction::Walk => &["steps"],
+            Instruction::Goto
             | Instruction::IfBox
             | Instruction::IfWall
             | Instruction::IfEdge
             | Instruction::IfNotBox
             | Instruction::IfNotWall
-            | Instruction::IfNotEdge => true,
+            | Instruction::IfNotEdge
+            | Instruction::IfBot
+            | Instruction::IfNotBot => &["cell"],
+            Instruction::Store => &["addr", "value"],
+            Instruction::Inc | Instruction::Dec => &["addr"],
+        }
+    }
+
+    /// Whether this instruction has any operands.
+    pub fn is_wide(self) -> bool {
+        !self.operands().is_empty()
+    }
+
+    /// Whether the first operand of this instruction is the cell it writes to.
+    pub fn is_memory_write(self) -> bool {
+        matches!(
+            self,
+            Instruction::Store | Instruction::Inc | Instruction::Dec
+        )
+    }
+
+    /// Whether the first operand of this instruction refers to a cell,
+    /// either as the target of a jump or as the cell it writes to.
+    pub fn is_address(self) -> bool {
+        self.is_branch() || self.is_memory_write()
+    }
+
+    /// Whether the operand of this instruction is the cell to jump to.
+    pub fn is_branch(self) -> bool {
+        match self {
+            Instruction::Halt
+            | Instruction::Walk
+            | Instruction::TurnAround
+            | Instruction::TurnLeft
+            | Instruction::TurnRight
+            | Instruction::Skip
+            | Instruction::Store
+            | Instruction::Inc
+            | Instruction::Dec => false,
+            Instruction::Goto
+            | Instruction::IfBox
+            | Instruction::IfWall
+            | Instruction::IfEdge
+            | Instruction::IfNotBox
+            | Instruction::IfNotWall
+            | Instruction::IfNotEdge
+            | Instruction::IfBot
+            | Instruction::IfNotBot => true,
+        }
+    }
+
+    /// What this instruction does, as explained in the manual.
+    pub fn manual(self) -> &'static str {
+        match self {
+            Instruction::Halt => {
+                "stops the bot for good, failing the level if it isn't on the exit"
+            }
+            Instruction::Walk => {
+                "walks forward one tile per tick, as many tiles as the operand says"
+            }
+            Instruction::TurnAround => "turns the bot around",
+            Instruction::TurnLeft => "turns the bot to its left",
+            Instruction::TurnRight => "turns the bot to its right",
+            Instruction::Skip => "waits for one tick",
+            Instruction::Goto => "continues with the instruction in the operand cell",
+            Instruction::IfBox => "jumps to the operand cell if there is a box in front of the bot",
+            Instruction::IfWall => {
+                "jumps to the operand cell if the bot faces a wall or a step up it can't climb"
+            }
+            Instruction::IfEdge => {
+                "jumps to the operand cell if the bot faces the void or a step down"
+            }
+            Instruction::IfNotBox => "jumps to the operand cell unless `if box` would",
+            Instruction::IfNotWall => "jumps to the operand cell unless `if wall` would",
+            Instruction::IfNotEdge => "jumps to the operand cell unless `if edge` would",
+            Instruction::IfBot => {
+                "jumps to the operand cell if there is another bot in front of the bot"
+            }
+            Instruction::IfNotBot => "jumps to the operand cell unless `if bot` would",
+            Instruction::Store => "writes the value into the cell at the address",
+            Instruction::Inc => "adds one to the cell at the address, wrapping from 31 to 0",
+            Instruction::Dec => "subtracts one from the cell at the address, wrapping from 0 to 31",
         }
     }
 
@@ -145,15 +276,22 @@ impl Instruction {
             | Instruction::TurnLeft
             | Instruction::TurnRight
             | Instruction::Skip
-            | Instruction::Goto => unreachable!(),
-            Instruction::IfBox | Instruction::IfWall | Instruction::IfEdge => true,
-            Instruction::IfNotBox | Instruction::IfNotWall | Instruction::IfNotEdge => false,
+            | Instruction::Goto
+            | Instruction::Store
+            | Instruction::Inc
+            | Instruction::Dec => unreachable!(),
+            Instruction::IfBox | Instruction::IfWall | Instruction::IfEdge | Instruction::IfBot => {
+                true
+            }
+            Instruction::IfNotBox
+            | Instruction::IfNotWall
+            | Instruction::IfNotEdge
+            | Instruction::IfNotBot => false,
         }
     }
 }
 
 pub fn run_bot_interpreter(
-    bot: &BotData,
     pos: GridPos,
     state: &mut BotState,
     map: &Map,
@@ -163,15 +301,10 @@ pub fn run_bot_interpreter(
         return;
     }
 
-    let facing_grid_pos = match state.dir {
-        Direction::Up => GridPos(pos.0, pos.1 - 1),
-        Direction::Down => GridPos(pos.0, pos.1 + 1),
-        Direction::Left => GridPos(pos.0 - 1, pos.1),
-        Direction::Right => GridPos(pos.0 + 1, pos.1),
-    };
+    let facing_grid_pos = pos.neighbour(state.dir);
 
     state.prev_instruction = state.current_instruction;
-    let instr = if let Some(instr) = state.read_instruction(bot) {
+    let instr = if let Some(instr) = state.read_instruction() {
         instr
     } else {
         state.halted = true;
@@ -181,7 +314,7 @@ pub fn run_bot_interpreter(
     match instr {
         Instruction::Halt => state.halted = true,
         Instruction::Walk => {
-            let arg = state.read_value(bot);
+            let arg = state.read_value();
             for _ in 0..arg {
                 state.steps.push(Step::Walk);
             }
@@ -217,7 +350,7 @@ pub fn run_bot_interpreter(
             state.steps.push(Step::Wait);
         }
         Instruction::Goto => {
-            let arg = state.read_value(bot);
+            let arg = state.read_value();
             state.current_instruction = arg;
         }
         Instruction::IfWall | Instruction::IfNotWall => {
@@ -225,7 +358,7 @@ pub fn run_bot_interpreter(
                 == (matches!(map.tile(facing_grid_pos), Place::Wall)
                     || (matches!(map.tile(pos), Place::LowerFloor)
                         && matches!(map.tile(facing_grid_pos), Place::UpperFloor)));
-            let target = state.read_value(bot);
+            let target = state.read_value();
 
             if to_jump_or_not_to_jump {
                 state.current_instruction = target;
@@ -236,7 +369,7 @@ pub fn run_bot_interpreter(
                 == ((matches!(map.tile(pos), Place::UpperFloor)
                     && matches!(map.tile(facing_grid_pos), Place::LowerFloor))
                     || matches!(map.tile(facing_grid_pos), Place::Void));
-            let target = state.read_value(bot);
+            let target = state.read_value();
 
             if to_jump_or_not_to_jump {
                 state.current_instruction = target;
@@ -246,277 +379,197 @@ pub fn run_bot_interpreter(
         Instruction::IfBox | Instruction::IfNotBox => {
             let cond =
                 instr.is_positive() == matches!(entity_on_tile_facing, Some(EntityKind::Box));
-            let target = state.read_value(bot);
+            let target = state.read_value();
 
             if cond {
                 state.current_instruction = target;
             }
         }
-    }
-}
+        Instruction::IfBot | Instruction::IfNotBot => {
+            let cond =
+                instr.is_positive() == matches!(entity_on_tile_facing, Some(EntityKind::Robot));
+            let target = state.read_value();
 
-fn dir_to_adjacent_tile(from: GridPos, to: GridPos) -> Direction {
-    if from.0 + 1 == to.0 {
-        return Direction::Right;
-    }
-    if from.0 == to.0 + 1 {
-        return Direction::Left;
-    }
-    if from.1 + 1 == to.1 {
-        return Direction::Down;
-    }
-    if from.1 == to.1 + 1 {
-        return Direction::Up;
+            if cond {
+                state.current_instruction = target;
+            }
+        }
+        Instruction::Store => {
+            let addr = state.read_value();
+            let value = state.read_value();
+            state.write_memory(addr, |_| value);
+        }
+        Instruction::Inc => {
+            let addr = state.read_value();
+            state.write_memory(addr, |value| (value % 32 + 1) % 32);
+        }
+        Instruction::Dec => {
+            let addr = state.read_value();
+            state.write_memory(addr, |value| (value % 32 + 31) % 32);
+        }
     }
-    panic!("bad inputs {:?} {:?}", from, to);
 }
 
-fn is_dirs_opposite(d1: Direction, d2: Direction) -> bool {
-    match (d1, d2) {
-        (Direction::Up, Direction::Down)
-        | (Direction::Down, Direction::Up)
-        | (Direction::Left, Direction::Right)
-        | (Direction::Right, Direction::Left) => true,
-        _ => false,
-    }
+/// Maps the entities of the current `Simulation` to the ECS.
+pub struct SimEntities {
+    bots: Vec<Entity>,
+    boxes: Vec<Entity>,
 }
 
-fn apply_bot_actions(
-    commands: &mut Commands,
-    bot_id: Entity,
-    map: &Map,
-    queries: &mut QuerySet<(
-        QueryState<(Entity, &BotData, &mut GridPos, &mut BotState)>,
-        QueryState<(Entity, &EntityKind, &GridPos), Without<VoidedOrExited>>,
-        QueryState<&mut GridPos>,
-    )>,
-) -> Vec<(Entity, draw::Step)> {
-    let mut render_steps = vec![];
-
-    let mut q = queries.q0();
-    let (_, _, cur_grid_pos, mut state) = q.get_mut(bot_id).unwrap();
-    let bot_action = if let Some(action) = state.steps.pop() {
-        action
-    } else {
-        return Vec::new();
-    };
-
-    match bot_action {
-        Step::Wait => render_steps.push((bot_id, draw::Step::Idle)),
-        Step::Walk => {
-            fn is_valid_move(
-                entity: Entity,
-                cur_tile_pos: GridPos,
-                cur_tile: Place,
-                tar_tile_pos: GridPos,
-                tar_tile: Place,
-                map: &Map,
-                blocking_entities: Query<(Entity, &EntityKind, &GridPos), Without<VoidedOrExited>>,
-            ) -> Vec<(Entity, draw::Step)> {
-                let valid_move = match cur_tile {
-                    Place::UpperFloor => match tar_tile {
-                        Place::LowerFloor | Place::UpperFloor | Place::Void | Place::Exit => true,
-                        Place::Ramp(ramp_dir) => {
-                            dir_to_adjacent_tile(cur_tile_pos, tar_tile_pos) == ramp_dir
-                        }
-                        Place::Wall => false,
-                    },
-                    Place::LowerFloor => match tar_tile {
-                        Place::Void | Place::LowerFloor | Place::Exit => true,
-                        Place::Ramp(ramp_dir) => {
-                            dir_to_adjacent_tile(tar_tile_pos, cur_tile_pos) == ramp_dir
-                        }
-                        Place::UpperFloor | Place::Wall => false,
-                    },
-                    Place::Ramp(ramp_dir) => match tar_tile {
-                        Place::Void | Place::Exit => true,
-                        Place::LowerFloor => {
-                            dir_to_adjacent_tile(cur_tile_pos, tar_tile_pos) == ramp_dir
-                        }
-                        Place::UpperFloor => {
-                            dir_to_adjacent_tile(tar_tile_pos, cur_tile_pos) == ramp_dir
-                        }
-                        Place::Ramp(tar_ramp_dir) => {
-                            is_dirs_opposite(ramp_dir, tar_ramp_dir)
-                                && (dir_to_adjacent_tile(cur_tile_pos, tar_tile_pos) == ramp_dir
-                                    || dir_to_adjacent_tile(cur_tile_pos, tar_tile_pos) == ramp_dir)
-                        }
-                        Place::Wall => false,
-                    },
-                    Place::Void => matches!(tar_tile, Place::Void),
-                    Place::Exit | Place::Wall => unreachable!(),
-                };
-
-                let mut steps = vec![];
-                if valid_move {
-                    steps.push((entity, draw::Step::Move(cur_tile_pos, tar_tile_pos)))
-                }
-                if let Some((e, _, pos)) = blocking_entities.iter().find(|(_, _, pos)| **pos == tar_tile_pos) && valid_move {
-                    let dir = dir_to_adjacent_tile(cur_tile_pos, tar_tile_pos);
-                    let new_tar_tile_pos = match dir {
-                        Direction::Up => GridPos(pos.0, pos.1 - 1),
-                        Direction::Down => GridPos(pos.0, pos.1 + 1),
-                        Direction::Left => GridPos(pos.0 - 1, pos.1),
-                        Direction::Right => GridPos(pos.0 + 1, pos.1),
-                    };
-                    let new_tar_tile = map.tile(new_tar_tile_pos);
-                    match &*is_valid_move(e, tar_tile_pos, tar_tile, new_tar_tile_pos, new_tar_tile, map, blocking_entities) {
-                        [] => steps.clear(),
-                        nested_steps @ [..] => steps.extend(nested_steps),
-                    };
-                }
-                steps
-            }
-
-            let tar_grid_pos = match state.dir {
-                Direction::Up => GridPos(cur_grid_pos.0, cur_grid_pos.1 - 1),
-                Direction::Down => GridPos(cur_grid_pos.0, cur_grid_pos.1 + 1),
-                Direction::Left => GridPos(cur_grid_pos.0 - 1, cur_grid_pos.1),
-                Direction::Right => GridPos(cur_grid_pos.0 + 1, cur_grid_pos.1),
-            };
-            let cur_tile = map.tile(*cur_grid_pos);
-            let tar_tile = map.tile(tar_grid_pos);
-            let steps = is_valid_move(
-                bot_id,
-                *cur_grid_pos,
-                cur_tile,
-                tar_grid_pos,
-                tar_tile,
-                map,
-                queries.q1(),
-            );
-
-            if let [] = &*steps {
-                render_steps.push((bot_id, draw::Step::MoveFail))
-            }
-
-            for (e, step) in steps {
-                render_steps.push((e, step));
-                if let draw::Step::Move(_, tar_pos) = step {
-                    if let Place::Void | Place::Exit = map.tile(tar_pos) {
-                        let mut q = queries.q0();
-                        if let Ok((_, _, _, mut state)) = q.get_mut(e) {
-                            state.steps.clear();
-                            state.halted = true;
-                        }
-                        commands.entity(e).insert(VoidedOrExited);
-                    }
-                    let mut q = queries.q2();
-                    *q.get_mut(e).unwrap() = tar_pos;
-                }
-            }
+impl SimEntities {
+    pub fn get(&self, entity: SimEntity) -> Entity {
+        match entity {
+            SimEntity::Bot(i) => self.bots[i],
+            SimEntity::Box(i) => self.boxes[i],
         }
-        Step::UpdateDir(dir) => {
-            render_steps.push((bot_id, draw::Step::UpdateDir(state.dir, dir)));
-            state.dir = dir;
+    }
+
+    /// Collects the bots, ordered by `BotData::index`, and the boxes of the current level.
+    pub fn from_world(world: &mut World) -> Self {
+        let mut bots = world
+            .query::<(Entity, &BotData)>()
+            .iter(world)
+            .map(|(e, data)| (e, data.index))
+            .collect::<Vec<_>>();
+        bots.sort_by_key(|&(_, index)| index);
+        let mut boxes = world
+            .query_filtered::<Entity, With<BoxData>>()
+            .iter(world)
+            .collect::<Vec<_>>();
+        boxes.sort();
+
+        SimEntities {
+            bots: bots.into_iter().map(|(e, _)| e).collect(),
+            boxes,
         }
     }
 
-    render_steps
+    pub fn bots(&self) -> &[Entity] {
+        &self.bots
+    }
+
+    pub fn boxes(&self) -> &[Entity] {
+        &self.boxes
+    }
 }
 
-pub fn entity_on_tile(
-    pos: GridPos,
-    q: Query<(Entity, &EntityKind, &GridPos), Without<VoidedOrExited>>,
-) -> Option<EntityKind> {
-    q.iter()
-        .find(|(_, _, pos2)| pos == **pos2)
-        .map(|(_, kind, _)| *kind)
+pub fn init_simulation(world: &mut World) {
+    let entities = SimEntities::from_world(world);
+    let bots = entities
+        .bots
+        .iter()
+        .map(|&e| {
+            let data = world.get::<BotData>(e).unwrap();
+            let pos = *world.get::<GridPos>(e).unwrap();
+            (data.instructions, pos, data.start_dir)
+        })
+        .collect::<Vec<_>>();
+    let boxes = entities
+        .boxes
+        .iter()
+        .map(|&e| *world.get::<GridPos>(e).unwrap())
+        .collect::<Vec<_>>();
+
+    let level = world.get_resource::<Level>().unwrap();
+    let sim = Simulation::new(level.map.clone(), level.bot_collision, bots, boxes);
+    world.insert_resource(Timeline::new(&sim));
+    world.insert_resource(sim);
+    world.insert_resource(entities);
 }
 
 pub fn progress_world(
-    mut commands: Commands,
     mut render_steps: ResMut<DrawUpdates>,
-    level: Res<Level>,
-    mut queries: QuerySet<(
-        QueryState<(Entity, &BotData, &mut GridPos, &mut BotState)>,
-        QueryState<(Entity, &EntityKind, &GridPos), Without<VoidedOrExited>>,
-        QueryState<&mut GridPos>,
-    )>,
+    mut sim: ResMut<Simulation>,
+    mut debugger: ResMut<Debugger>,
+    mut timeline: ResMut<Timeline>,
+    speed: Res<SimSpeed>,
+    entities: Res<SimEntities>,
+    mut positions: Query<&mut GridPos>,
 ) {
-    if let 0 = &render_steps.data.len() {
-    } else {
+    if !render_steps.data.is_empty() {
         return;
     }
 
-    let map = &level.map;
-    let mut bots = queries
-        .q0()
-        .iter()
-        .map(|(e, _, _, _)| e)
-        .collect::<Vec<Entity>>();
-
-    bots.sort();
-    for bot_id in bots {
-        let q = queries.q0();
-        let (_, _, pos, state) = q.get(bot_id).unwrap();
-        let viewing_pos = match state.dir {
-            Direction::Up => GridPos(pos.0, pos.1 - 1),
-            Direction::Down => GridPos(pos.0, pos.1 + 1),
-            Direction::Left => GridPos(pos.0 - 1, pos.1),
-            Direction::Right => GridPos(pos.0 + 1, pos.1),
-        };
-        let entity_kind = entity_on_tile(viewing_pos, queries.q1());
-        let mut q = queries.q0();
-        let (_, bot, pos, mut state) = q.get_mut(bot_id).unwrap();
-
-        run_bot_interpreter(bot, *pos, &mut *state, map, entity_kind);
-        let changes = apply_bot_actions(&mut commands, bot_id, map, &mut queries);
-        render_steps.data.push_back(changes);
+    // without animations, many ticks are simulated in each frame.
+    let ticks = if speed.is_instant() {
+        SimSpeed::INSTANT_TICKS_PER_FRAME
+    } else {
+        1
+    };
+    for tick in 0..ticks {
+        // stop fast-forwarding once the outcome is known.
+        if (tick > 0 && sim.outcome().is_some()) || !debugger.should_step(&sim) {
+            break;
+        }
+
+        let steps = sim.step();
+        for changes in &steps {
+            let changes = changes
+                .iter()
+                .map(|&(e, step)| (entities.get(e), step))
+                .collect();
+            render_steps.data.push_back(changes);
+        }
+        timeline.record(&sim, steps);
+    }
+
+    let bots = iter::zip(&entities.bots, sim.bots.iter().map(|b| b.pos));
+    let boxes = iter::zip(&entities.boxes, sim.boxes.iter().map(|b| b.pos));
+    for (&entity, pos) in bots.chain(boxes) {
+        *positions.get_mut(entity).unwrap() = pos;
     }
 }
 
-pub fn failure_detector(
-    mut commands: Commands,
-    q: Query<(&GridPos, &EntityKind)>,
-    bot_state: Query<&BotState>,
-    level: Res<Level>,
-) {
-    if q.iter().any(|(pos, kind)| {
-        matches!(kind, EntityKind::Robot) && matches!(level.map.tile(*pos), Place::Void)
-    }) {
-        commands.insert_resource(ExecutionFailure(format!(
-            "stage failed: the robot fell into the void and will not make further progress"
-        )));
-    } else if q.iter().any(|(pos, kind)| {
-        matches!(kind, EntityKind::Robot) && matches!(level.map.tile(*pos), Place::Exit)
-    }) {
-        commands.insert_resource(ExecutionFailure(format!(
-            "stage failed: the robot entered the exit without first inserting all boxes"
-        )));
-    } else if bot_state.iter().any(|st| st.halted) {
-        commands.insert_resource(ExecutionFailure(format!(
-            "stage failed: the robot halted and will not make further progress"
-        )));
-    } else if q.iter().any(|(pos, kind)| {
-        matches!(kind, EntityKind::Box) && matches!(level.map.tile(*pos), Place::Void)
-    }) {
-        commands.insert_resource(ExecutionFailure(format!(
-            "stage failed: a box fell into the void prevent a successful finish"
-        )));
+pub fn failure_detector(mut commands: Commands, sim: Res<Simulation>) {
+    // the simulation may be rewound to before the failure.
+    match sim.outcome() {
+        Some(Outcome::Failed(failure)) => commands.insert_resource(failure),
+        _ => commands.remove_resource::<ExecutionFailure>(),
     }
 }
 
+/// Records a win and offers to continue with the next level,
+/// going back to the start screen if there is none.
 pub fn level_complete_checker(
+    commands: Commands,
+    asset_server: Res<AssetServer>,
     mut state: ResMut<State<GameState>>,
-    q: Query<(&GridPos, &EntityKind)>,
-    level: Res<Level>,
+    sim: Res<Simulation>,
     mut level_list: ResMut<LevelList>,
     current_level: Res<CurrentLevel>,
+    mut save: ResMut<SaveGame>,
+    next_level: Option<Res<NextLevelButton>>,
+    timeline: Res<Timeline>,
+    error: Res<ErrorText>,
+    mut color: Query<&mut UiColor>,
+    children: Query<&Children>,
+    mut text: Query<&mut Text>,
 ) {
-    let level_won = q
-        .iter()
-        .all(|(pos, _)| matches!(level.map.tile(*pos), Place::Exit));
-    if level_won {
-        level_list.beaten[current_level.0] = true;
-        state.set(GameState::StartScreen).unwrap();
+    // the win has already been recorded.
+    if next_level.is_some() {
+        return;
     }
-}
 
-pub fn init_state(mut commands: Commands, bots: Query<(Entity, &BotData)>) {
-    for (e, data) in bots.iter() {
-        commands.entity(e).insert(BotState::new(data.start_dir));
+    if let Some(Outcome::Won) = sim.outcome() {
+        level_list.beaten[current_level.0] = true;
+        let programs = sim.bots.iter().map(|b| b.instructions).collect::<Vec<_>>();
+        let name = &level_list.levels[current_level.0].name;
+        save.record_win(name, sim.tick, &programs);
+        save.store();
+
+        let level = &level_list.levels[current_level.0];
+        let replay = Replay::new(level, &programs, timeline.steps(sim.tick).to_vec());
+        let path = Replay::path(level, ReplayKind::Won);
+        if let Err(e) = path.and_then(|path| replay.save(&path)) {
+            error.show(e, &mut color, &children, &mut text);
+        }
+        if level_list.next_level(current_level.0).is_some() {
+            ui::add_button::<NextLevelButton>(commands, asset_server);
+        } else {
+            state.set(GameState::StartScreen).unwrap();
+        }
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct ExecutionFailure(pub String);
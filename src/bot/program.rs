@@ -0,0 +1,309 @@
+use std::fmt::{self, Display, Write};
+use std::path::{Path, PathBuf};
+
+use crate::bot::{asm, Instruction};
+use crate::map::Level;
+
+#[derive(Debug, Clone)]
+pub struct ProgramError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl Display for ProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+impl std::error::Error for ProgramError {}
+
+/// Parses the value of a single memory cell.
+pub fn parse_value(src: &str) -> Result<u8, String> {
+    match src.parse::<u128>() {
+        Ok(v) => {
+            if v < 32 {
+                Ok(v as u8)
+            } else {
+                Err(format!(
+                    "the value `{}` cannot be stored as it is larger than 31",
+                    v
+                ))
+            }
+        }
+        // TODO: improve error msg.
+        Err(e) => Err(format!("{}", e)),
+    }
+}
+
+/// Parses an instruction from the start of `words`, leaving all
+/// words after the instruction in the iterator.
+pub fn parse_instruction_words<'a>(
+    words: &mut impl Iterator<Item = &'a str>,
+) -> Result<Instruction, String> {
+    Ok(match words.next() {
+        Some("halt") => Instruction::Halt,
+        Some("walk") => Instruction::Walk,
+        Some("turn") => match words.next() {
+            Some("around") => Instruction::TurnAround,
+            Some("left") => Instruction::TurnLeft,
+            Some("right") => Instruction::TurnRight,
+            found => {
+                return Err(format!(
+                    "invalid `turn` command, \
+                    expected one of `turn around`, `turn right`, or \
+                    `turn left`. found `turn{}`",
+                    found.map_or(String::new(), |w| format!(" {}", w))
+                ));
+            }
+        },
+        Some("skip") => Instruction::Skip,
+        Some("goto") => Instruction::Goto,
+        Some("store") => Instruction::Store,
+        Some("inc") => Instruction::Inc,
+        Some("dec") => Instruction::Dec,
+        Some("if") => {
+            let mut next = words.next();
+            let negate = next == Some("not");
+            if negate {
+                next = words.next();
+            }
+
+            const BRANCH_COND_EXP: &str = "expected one of `box`, `wall`, `edge`, or `bot`";
+            match next {
+                Some("box") => {
+                    if negate {
+                        Instruction::IfNotBox
+                    } else {
+                        Instruction::IfBox
+                    }
+                }
+                Some("wall") => {
+                    if negate {
+                        Instruction::IfNotWall
+                    } else {
+                        Instruction::IfWall
+                    }
+                }
+                Some("edge") => {
+                    if negate {
+                        Instruction::IfNotEdge
+                    } else {
+                        Instruction::IfEdge
+                    }
+                }
+                Some("bot") => {
+                    if negate {
+                        Instruction::IfNotBot
+                    } else {
+                        Instruction::IfBot
+                    }
+                }
+                Some(e) => {
+                    return Err(format!(
+                        "invalid branch condition, {}, found `{}`",
+                        BRANCH_COND_EXP, e
+                    ));
+                }
+                None => {
+                    return Err(format!("missing branch condition, {}", BRANCH_COND_EXP));
+                }
+            }
+        }
+        _ => {
+            return Err(String::from(
+                "invalid start of command, \
+                expected one of `halt`, `walk`, `turn`, `skip`, `goto`, \
+                `if`, `store`, `inc`, or `dec`. For more info about the available instructions, \
+                refer to the manual, which is opened with `F1`.",
+            ));
+        }
+    })
+}
+
+pub fn parse_instruction(src: &str) -> Result<Instruction, String> {
+    let mut words = src.split_whitespace();
+    let instr = parse_instruction_words(&mut words)?;
+    if let Some(s) = words.next() {
+        return Err(format!(
+            "unexpected word `{}`, `{}` is already a complete instruction",
+            s, instr
+        ));
+    }
+
+    Ok(instr)
+}
+
+/// Parses a single memory cell, which is either a value or the name of an instruction.
+pub fn parse_cell(src: &str) -> Result<u8, String> {
+    if src.starts_with(|c: char| c.is_ascii_digit()) {
+        parse_value(src)
+    } else {
+        parse_instruction(src).map(|i| i.repr())
+    }
+}
+
+/// Parses a `.bot` program file containing one memory cell per line.
+///
+/// Blank lines are ignored and all cells after the end of the file are `halt`.
+pub fn parse(src: &str) -> Result<[u8; 32], ProgramError> {
+    let mut instructions = [0; 32];
+    let mut cell = 0;
+    for (i, line) in src.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let err = |msg| ProgramError { line: i + 1, msg };
+        if cell == instructions.len() {
+            return Err(err(format!(
+                "the program does not fit into the {} available memory cells",
+                instructions.len()
+            )));
+        }
+        instructions[cell] = parse_cell(line).map_err(err)?;
+        cell += 1;
+    }
+
+    Ok(instructions)
+}
+
+/// Which cells are an operand of the instruction before them,
+/// reading the program from the first cell onwards.
+pub fn operand_cells(instructions: &[u8; 32]) -> [bool; 32] {
+    let mut operands = [false; 32];
+    let mut cell = 0;
+    while cell < instructions.len() {
+        let len = Instruction::from_repr(instructions[cell]).map_or(0, |i| i.operands().len());
+        for operand in operands.iter_mut().skip(cell + 1).take(len) {
+            *operand = true;
+        }
+        cell += 1 + len;
+    }
+    operands
+}
+
+/// The cells holding the address of another cell, which is either the jump
+/// target of a `goto` or `if` or the cell written by `store`, `inc` or `dec`.
+///
+/// This is always the first operand, see `operand_cells`.
+pub fn address_operands(instructions: &[u8; 32]) -> impl Iterator<Item = usize> + '_ {
+    let operands = operand_cells(instructions);
+    (1..instructions.len()).filter(move |&cell| {
+        operands[cell]
+            && !operands[cell - 1]
+            && Instruction::from_repr(instructions[cell - 1]).map_or(false, |i| i.is_address())
+    })
+}
+
+/// The number of cells up to and including the last one which isn't `halt`.
+pub fn used_cells(instructions: &[u8; 32]) -> usize {
+    instructions
+        .iter()
+        .rposition(|&i| i != Instruction::Halt.repr())
+        .map_or(0, |i| i + 1)
+}
+
+/// Checks that `instructions` fit into the memory available in `level`.
+pub fn check_memory(level: &Level, instructions: &[u8; 32]) -> Result<(), String> {
+    let used = used_cells(instructions);
+    if used > level.meta.memory {
+        Err(format!(
+            "the program uses {} memory cells, but this level only has {}",
+            used, level.meta.memory
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Renders `instructions` in the format accepted by `parse`.
+///
+/// Operands and cells which aren't valid instructions are written as values,
+/// all other cells as instruction names. Trailing `halt` cells are omitted.
+pub fn render(instructions: &[u8; 32]) -> String {
+    let len = used_cells(instructions);
+    let operands = operand_cells(instructions);
+    let mut out = String::new();
+    for (&value, &is_operand) in instructions[..len].iter().zip(&operands) {
+        match Instruction::from_repr(value) {
+            Some(instr) if !is_operand => writeln!(out, "{}", instr).unwrap(),
+            _ => writeln!(out, "{}", value).unwrap(),
+        }
+    }
+    out
+}
+
+/// Where the program of the `bot`-th bot of `level` is saved.
+pub fn level_path(level: &Level, bot: usize) -> PathBuf {
+    let file = if level.bots.len() > 1 {
+        format!("{}.{}.bot", level.name, bot)
+    } else {
+        format!("{}.bot", level.name)
+    };
+    PathBuf::from("programs").join(file)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save(path: &Path, instructions: &[u8; 32]) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("failed to create {:?}: {}", dir, e))?;
+    }
+    std::fs::write(path, render(instructions))
+        .map_err(|e| format!("failed to write {:?}: {}", path, e))
+}
+
+/// Reads a raw memory image of at most 32 bytes, each smaller than 32.
+pub fn from_bytes(bytes: &[u8]) -> Result<[u8; 32], String> {
+    let mut instructions = [0; 32];
+    if bytes.len() > instructions.len() {
+        return Err(format!(
+            "the memory image has {} bytes but there are only {} memory cells",
+            bytes.len(),
+            instructions.len()
+        ));
+    }
+    if let Some(cell) = bytes.iter().position(|&b| b >= 32) {
+        return Err(format!(
+            "the value `{}` in cell {} is larger than 31",
+            bytes[cell], cell
+        ));
+    }
+    instructions[..bytes.len()].copy_from_slice(bytes);
+    Ok(instructions)
+}
+
+/// Loads a program, assembling `.asm` files and reading `.bin` files
+/// as a raw memory image. All other files use the `.bot` format.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load(path: &Path) -> Result<[u8; 32], String> {
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    if extension == Some("bin") {
+        let bytes = std::fs::read(path).map_err(|e| format!("failed to read {:?}: {}", path, e))?;
+        return from_bytes(&bytes).map_err(|e| format!("invalid program {:?}, {}", path, e));
+    }
+
+    let src =
+        std::fs::read_to_string(path).map_err(|e| format!("failed to read {:?}: {}", path, e))?;
+    let program = if extension == Some("asm") {
+        asm::assemble(&src)
+    } else {
+        parse(&src)
+    };
+    program.map_err(|e| format!("invalid program {:?}, {}", path, e))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn save(_: &Path, _: &[u8; 32]) -> Result<(), String> {
+    Err(String::from(
+        "saving programs is not supported in the browser",
+    ))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn load(_: &Path) -> Result<[u8; 32], String> {
+    Err(String::from(
+        "loading programs is not supported in the browser",
+    ))
+}
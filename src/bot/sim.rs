@@ -0,0 +1,691 @@
+use std::collections::HashMap;
+
+use crate::bot::{run_bot_interpreter, BotState, ExecutionFailure, Instruction, Step};
+use crate::draw;
+use crate::map::{BotCollision, EntityKind, GridPos, Level, Map, Place};
+use crate::Direction;
+use serde::{Deserialize, Serialize};
+
+/// An entity taking part in a `Simulation`, identified by its index
+/// in `Simulation::bots` or `Simulation::boxes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SimEntity {
+    Bot(usize),
+    Box(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct SimBot {
+    /// The program the bot started with, see `BotState::memory` for its current memory.
+    pub instructions: [u8; 32],
+    pub pos: GridPos,
+    pub state: BotState,
+    pub voided_or_exited: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SimBox {
+    pub pos: GridPos,
+    pub voided_or_exited: bool,
+}
+
+/// A sequence of ticks after which the world is back in the same state.
+#[derive(Debug, Clone)]
+pub struct Cycle {
+    pub len: u64,
+    /// The cells of the instructions executed by each bot during the cycle.
+    pub cells: Vec<Vec<u8>>,
+}
+
+/// The position of a bot, its registers, its remaining steps and its memory.
+type BotKey = ((GridPos, bool), (bool, u8, Direction), Vec<Step>, [u8; 32]);
+
+/// The state compared to find cycles, see `Simulation::state`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct State {
+    bots: Vec<BotKey>,
+    boxes: Vec<(GridPos, bool)>,
+}
+
+/// The state of a `Simulation` after some tick, see `Simulation::restore`.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub tick: u64,
+    pub bots: Vec<SimBot>,
+    pub boxes: Vec<SimBox>,
+    cycle: Option<Cycle>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    Won,
+    Failed(ExecutionFailure),
+}
+
+/// The rules of the game without any dependency on the ECS.
+///
+/// Each call to `step` advances the world by one tick and returns
+/// the resulting render steps, one batch per bot.
+///
+/// During a tick all bots first decide on their action based on the world
+/// at the start of the tick. Bots which walk onto the same tile or into each
+/// other collide head-on and stay in place. The pushes of all other bots are
+/// planned on the same world, and bots whose pushes would move an entity to
+/// different tiles or different entities onto the same tile stay in place as well.
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    pub map: Map,
+    pub bot_collision: BotCollision,
+    pub bots: Vec<SimBot>,
+    pub boxes: Vec<SimBox>,
+    pub tick: u64,
+    /// The tick at which each previously seen state was first reached.
+    seen: HashMap<State, u64>,
+    pub cycle: Option<Cycle>,
+}
+
+impl Simulation {
+    pub fn new(
+        map: Map,
+        bot_collision: BotCollision,
+        bots: impl IntoIterator<Item = ([u8; 32], GridPos, Direction)>,
+        boxes: impl IntoIterator<Item = GridPos>,
+    ) -> Self {
+        let mut sim = Simulation {
+            map,
+            bot_collision,
+            bots: bots
+                .into_iter()
+                .map(|(instructions, pos, dir)| SimBot {
+                    instructions,
+                    pos,
+                    state: BotState::new(dir, instructions),
+                    voided_or_exited: false,
+                })
+                .collect(),
+            boxes: boxes
+                .into_iter()
+                .map(|pos| SimBox {
+                    pos,
+                    voided_or_exited: false,
+                })
+                .collect(),
+            tick: 0,
+            seen: HashMap::new(),
+            cycle: None,
+        };
+        sim.seen.insert(sim.state(), 0);
+        sim
+    }
+
+    /// Starts `level` with `programs[i]` running on the `i`-th bot.
+    ///
+    /// Bots without a program only contain `halt`.
+    pub fn from_level(level: &Level, programs: &[[u8; 32]]) -> Self {
+        let program = |i| {
+            programs
+                .get(i)
+                .copied()
+                .unwrap_or([Instruction::Halt.repr(); 32])
+        };
+        Simulation::new(
+            level.map.clone(),
+            level.bot_collision,
+            level
+                .bots
+                .iter()
+                .enumerate()
+                .map(|(i, &(pos, dir))| (program(i), pos, dir)),
+            level.boxes.iter().copied(),
+        )
+    }
+
+    pub fn step(&mut self) -> Vec<Vec<(SimEntity, draw::Step)>> {
+        let batches = self.advance();
+        if self.cycle.is_none() {
+            let state = self.state();
+            if let Some(&start) = self.seen.get(&state) {
+                self.cycle = Some(self.find_cycle(self.tick - start));
+            } else {
+                self.seen.insert(state, self.tick);
+            }
+        }
+        batches
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            tick: self.tick,
+            bots: self.bots.clone(),
+            boxes: self.boxes.clone(),
+            cycle: self.cycle.clone(),
+        }
+    }
+
+    /// Rewinds the simulation to an earlier `snapshot` of itself.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.tick = snapshot.tick;
+        self.bots = snapshot.bots.clone();
+        self.boxes = snapshot.boxes.clone();
+        self.cycle = snapshot.cycle.clone();
+        self.seen.retain(|_, &mut tick| tick <= snapshot.tick);
+    }
+
+    /// Everything which influences the future of the simulation.
+    fn state(&self) -> State {
+        State {
+            bots: self
+                .bots
+                .iter()
+                .map(|bot| {
+                    let state = &bot.state;
+                    (
+                        (bot.pos, bot.voided_or_exited),
+                        (state.halted, state.current_instruction, state.dir),
+                        state.steps.clone(),
+                        state.memory,
+                    )
+                })
+                .collect(),
+            boxes: self
+                .boxes
+                .iter()
+                .map(|b| (b.pos, b.voided_or_exited))
+                .collect(),
+        }
+    }
+
+    /// Replays the `len` ticks after the current one, which
+    /// are the same as the ones leading up to it.
+    fn find_cycle(&self, len: u64) -> Cycle {
+        let mut sim = self.clone();
+        let mut cells = vec![Vec::new(); sim.bots.len()];
+        for _ in 0..len {
+            for (cells, bot) in cells.iter_mut().zip(&sim.bots) {
+                if let Some(cell) = bot.state.next_instruction() {
+                    cells.push(cell);
+                }
+            }
+            sim.advance();
+        }
+        for cells in &mut cells {
+            cells.sort_unstable();
+            cells.dedup();
+        }
+        Cycle { len, cells }
+    }
+
+    fn advance(&mut self) -> Vec<Vec<(SimEntity, draw::Step)>> {
+        self.tick += 1;
+        for bot in 0..self.bots.len() {
+            let viewing_pos = self.bots[bot].pos.neighbour(self.bots[bot].state.dir);
+            let entity_kind = self.entity_on_tile(viewing_pos);
+            let SimBot { pos, state, .. } = &mut self.bots[bot];
+            run_bot_interpreter(*pos, state, &self.map, entity_kind);
+        }
+
+        // all moves are planned on the world at the start of the tick,
+        // so that they don't depend on the order of the bots.
+        let collided = self.head_on_collisions();
+        let actions = self
+            .bots
+            .iter_mut()
+            .map(|bot| bot.state.steps.pop())
+            .collect::<Vec<_>>();
+        let mut moves = actions
+            .iter()
+            .zip(collided)
+            .enumerate()
+            .map(|(bot, (action, collided))| match action {
+                Some(Step::Walk) if !collided => {
+                    let pos = self.bots[bot].pos;
+                    let target = pos.neighbour(self.bots[bot].state.dir);
+                    self.is_valid_move(SimEntity::Bot(bot), pos, target)
+                }
+                _ => Vec::new(),
+            })
+            .collect::<Vec<_>>();
+        let conflicts = (0..moves.len())
+            .map(|i| (0..moves.len()).any(|j| i != j && conflicting(&moves[i], &moves[j])))
+            .collect::<Vec<_>>();
+        for (moves, conflict) in moves.iter_mut().zip(conflicts) {
+            if conflict {
+                moves.clear();
+            }
+        }
+
+        let mut moved = Vec::new();
+        let mut batches = Vec::with_capacity(self.bots.len());
+        for (bot, (action, moves)) in actions.into_iter().zip(moves).enumerate() {
+            batches.push(self.apply_bot_action(bot, action, moves, &mut moved));
+        }
+        batches
+    }
+
+    /// Which bots walk onto the same tile as another bot or
+    /// onto the tile of a bot walking towards them in this tick.
+    fn head_on_collisions(&self) -> Vec<bool> {
+        let targets = self
+            .bots
+            .iter()
+            .map(|bot| match bot.state.steps.last() {
+                Some(Step::Walk) if !bot.voided_or_exited => Some(bot.pos.neighbour(bot.state.dir)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        (0..self.bots.len())
+            .map(|i| {
+                targets[i].map_or(false, |target| {
+                    (0..self.bots.len()).any(|j| {
+                        i != j
+                            && (targets[j] == Some(target)
+                                || (self.bots[j].pos == target
+                                    && targets[j] == Some(self.bots[i].pos)))
+                    })
+                })
+            })
+            .collect()
+    }
+
+    pub fn entity_on_tile(&self, pos: GridPos) -> Option<EntityKind> {
+        self.entity_at(pos).map(|e| match e {
+            SimEntity::Bot(_) => EntityKind::Robot,
+            SimEntity::Box(_) => EntityKind::Box,
+        })
+    }
+
+    fn entity_at(&self, pos: GridPos) -> Option<SimEntity> {
+        let bot = self
+            .bots
+            .iter()
+            .position(|b| !b.voided_or_exited && b.pos == pos)
+            .map(SimEntity::Bot);
+        bot.or_else(|| {
+            self.boxes
+                .iter()
+                .position(|b| !b.voided_or_exited && b.pos == pos)
+                .map(SimEntity::Box)
+        })
+    }
+
+    fn is_valid_move(
+        &self,
+        entity: SimEntity,
+        cur_tile_pos: GridPos,
+        tar_tile_pos: GridPos,
+    ) -> Vec<(SimEntity, draw::Step)> {
+        let valid_move = can_move(&self.map, cur_tile_pos, tar_tile_pos);
+
+        let mut steps = vec![];
+        if valid_move {
+            steps.push((entity, draw::Step::Move(cur_tile_pos, tar_tile_pos)));
+            if let Some(e) = self.entity_at(tar_tile_pos) {
+                if let (SimEntity::Bot(_), BotCollision::Block) = (e, self.bot_collision) {
+                    steps.clear();
+                    return steps;
+                }
+                let dir = dir_to_adjacent_tile(cur_tile_pos, tar_tile_pos);
+                let new_tar_tile_pos = tar_tile_pos.neighbour(dir);
+                match &*self.is_valid_move(e, tar_tile_pos, new_tar_tile_pos) {
+                    [] => steps.clear(),
+                    nested_steps @ [..] => steps.extend(nested_steps),
+                }
+            }
+        }
+        steps
+    }
+
+    /// Applies the action of `bot`, with `moves` being the planned moves if it walks.
+    ///
+    /// Entities pushed by several bots in the same direction are only moved once.
+    fn apply_bot_action(
+        &mut self,
+        bot: usize,
+        action: Option<Step>,
+        moves: Vec<(SimEntity, draw::Step)>,
+        moved: &mut Vec<SimEntity>,
+    ) -> Vec<(SimEntity, draw::Step)> {
+        let mut render_steps = vec![];
+        match action {
+            None => {}
+            Some(Step::Wait) => render_steps.push((SimEntity::Bot(bot), draw::Step::Idle)),
+            Some(Step::Walk) => {
+                if moves.is_empty() {
+                    render_steps.push((SimEntity::Bot(bot), draw::Step::MoveFail))
+                }
+
+                for (e, step) in moves {
+                    if moved.contains(&e) {
+                        continue;
+                    }
+                    moved.push(e);
+                    render_steps.push((e, step));
+                    if let draw::Step::Move(_, tar_pos) = step {
+                        let voided_or_exited =
+                            matches!(self.map.tile(tar_pos), Place::Void | Place::Exit);
+                        match e {
+                            SimEntity::Bot(i) => {
+                                let bot = &mut self.bots[i];
+                                if voided_or_exited {
+                                    bot.state.steps.clear();
+                                    bot.state.halted = true;
+                                    bot.voided_or_exited = true;
+                                }
+                                bot.pos = tar_pos;
+                            }
+                            SimEntity::Box(i) => {
+                                let b = &mut self.boxes[i];
+                                b.voided_or_exited |= voided_or_exited;
+                                b.pos = tar_pos;
+                            }
+                        }
+                    }
+                }
+            }
+            Some(Step::UpdateDir(dir)) => {
+                let state = &mut self.bots[bot].state;
+                render_steps.push((SimEntity::Bot(bot), draw::Step::UpdateDir(state.dir, dir)));
+                state.dir = dir;
+            }
+        }
+
+        render_steps
+    }
+
+    /// Whether the level has been won or can no longer be won.
+    pub fn outcome(&self) -> Option<Outcome> {
+        let on_void = |pos| matches!(self.map.tile(pos), Place::Void);
+        let on_exit = |pos| matches!(self.map.tile(pos), Place::Exit);
+        let bot_positions = || self.bots.iter().map(|b| b.pos);
+        let box_positions = || self.boxes.iter().map(|b| b.pos);
+
+        if bot_positions().chain(box_positions()).all(on_exit) {
+            return Some(Outcome::Won);
+        }
+
+        let failure = if bot_positions().any(on_void) {
+            "stage failed: the robot fell into the void and will not make further progress"
+        } else if bot_positions().any(on_exit) {
+            "stage failed: the robot entered the exit without first inserting all boxes"
+        } else if self.bots.iter().any(|b| b.state.halted) {
+            "stage failed: the robot halted and will not make further progress"
+        } else if box_positions().any(on_void) {
+            "stage failed: a box fell into the void prevent a successful finish"
+        } else if let Some(cycle) = &self.cycle {
+            return Some(Outcome::Failed(ExecutionFailure(format!(
+                "stage failed: the program loops forever without progress, \
+                repeating a cycle of {} tick{} while executing {}",
+                cycle.len,
+                if cycle.len == 1 { "" } else { "s" },
+                self.describe_cells(&cycle.cells)
+            ))));
+        } else {
+            return None;
+        };
+
+        Some(Outcome::Failed(ExecutionFailure(failure.to_string())))
+    }
+
+    fn describe_cells(&self, cells: &[Vec<u8>]) -> String {
+        let per_bot = cells.iter().zip(&self.bots).map(|(cells, bot)| {
+            let instrs = cells
+                .iter()
+                .map(|&cell| {
+                    match bot
+                        .state
+                        .memory
+                        .get(cell as usize)
+                        .and_then(|&i| Instruction::from_repr(i))
+                    {
+                        Some(instr) => format!("`{}` at {}", instr, cell),
+                        None => format!("cell {}", cell),
+                    }
+                })
+                .collect::<Vec<_>>();
+            if instrs.is_empty() {
+                String::from("nothing")
+            } else {
+                instrs.join(", ")
+            }
+        });
+
+        if self.bots.len() == 1 {
+            per_bot.collect()
+        } else {
+            per_bot
+                .enumerate()
+                .map(|(i, instrs)| format!("{} for bot {}", instrs, i))
+                .collect::<Vec<_>>()
+                .join("; ")
+        }
+    }
+}
+
+fn can_move(map: &Map, cur_tile_pos: GridPos, tar_tile_pos: GridPos) -> bool {
+    match map.tile(cur_tile_pos) {
+        Place::UpperFloor => match map.tile(tar_tile_pos) {
+            Place::LowerFloor | Place::UpperFloor | Place::Void | Place::Exit => true,
+            Place::Ramp(ramp_dir) => dir_to_adjacent_tile(cur_tile_pos, tar_tile_pos) == ramp_dir,
+            Place::Wall => false,
+        },
+        Place::LowerFloor => match map.tile(tar_tile_pos) {
+            Place::Void | Place::LowerFloor | Place::Exit => true,
+            Place::Ramp(ramp_dir) => dir_to_adjacent_tile(tar_tile_pos, cur_tile_pos) == ramp_dir,
+            Place::UpperFloor | Place::Wall => false,
+        },
+        Place::Ramp(ramp_dir) => match map.tile(tar_tile_pos) {
+            Place::Void | Place::Exit => true,
+            Place::LowerFloor => dir_to_adjacent_tile(cur_tile_pos, tar_tile_pos) == ramp_dir,
+            Place::UpperFloor => dir_to_adjacent_tile(tar_tile_pos, cur_tile_pos) == ramp_dir,
+            Place::Ramp(tar_ramp_dir) => {
+                is_dirs_opposite(ramp_dir, tar_ramp_dir)
+                    && dir_to_adjacent_tile(cur_tile_pos, tar_tile_pos) == ramp_dir
+            }
+            Place::Wall => false,
+        },
+        Place::Void => matches!(map.tile(tar_tile_pos), Place::Void),
+        Place::Exit | Place::Wall => unreachable!(),
+    }
+}
+
+/// Whether the planned moves of two bots can't both happen, as they either move
+/// the same entity to different tiles or different entities onto the same tile.
+fn conflicting(a: &[(SimEntity, draw::Step)], b: &[(SimEntity, draw::Step)]) -> bool {
+    a.iter().any(|&(e1, s1)| {
+        b.iter().any(|&(e2, s2)| match (s1, s2) {
+            (draw::Step::Move(_, t1), draw::Step::Move(_, t2)) => (e1 == e2) != (t1 == t2),
+            _ => false,
+        })
+    })
+}
+
+fn dir_to_adjacent_tile(from: GridPos, to: GridPos) -> Direction {
+    if from.0.wrapping_add(1) == to.0 {
+        return Direction::Right;
+    }
+    if from.0 == to.0.wrapping_add(1) {
+        return Direction::Left;
+    }
+    if from.1.wrapping_add(1) == to.1 {
+        return Direction::Down;
+    }
+    if from.1 == to.1.wrapping_add(1) {
+        return Direction::Up;
+    }
+    panic!("bad inputs {:?} {:?}", from, to);
+}
+
+fn is_dirs_opposite(d1: Direction, d2: Direction) -> bool {
+    matches!(
+        (d1, d2),
+        (Direction::Up, Direction::Down)
+            | (Direction::Down, Direction::Up)
+            | (Direction::Left, Direction::Right)
+            | (Direction::Right, Direction::Left)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::asm;
+
+    /// Runs the programs on `map` until the level is won or failed.
+    fn run(map: &str, bots: &[(&str, GridPos, Direction)], boxes: &[GridPos]) -> Simulation {
+        let map = Map::from_str(map).unwrap();
+        let bots = bots
+            .iter()
+            .map(|&(src, pos, dir)| (asm::assemble(src).unwrap(), pos, dir));
+        let mut sim = Simulation::new(map, BotCollision::Push, bots, boxes.iter().copied());
+        while sim.outcome().is_none() {
+            assert!(sim.tick < 100, "the simulation didn't finish");
+            sim.step();
+        }
+        sim
+    }
+
+    fn bot_positions(sim: &Simulation) -> Vec<GridPos> {
+        sim.bots.iter().map(|b| b.pos).collect()
+    }
+
+    fn box_positions(sim: &Simulation) -> Vec<GridPos> {
+        sim.boxes.iter().map(|b| b.pos).collect()
+    }
+
+    fn failure(sim: &Simulation) -> String {
+        match sim.outcome() {
+            Some(Outcome::Failed(ExecutionFailure(msg))) => msg,
+            outcome => panic!("expected a failure, got {:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn walk_to_exit() {
+        let sim = run("..o", &[("walk 2", GridPos(0, 0), Direction::Right)], &[]);
+        assert!(matches!(sim.outcome(), Some(Outcome::Won)));
+        assert_eq!(bot_positions(&sim), [GridPos(2, 0)]);
+    }
+
+    #[test]
+    fn push_row_of_boxes() {
+        let boxes = [GridPos(1, 0), GridPos(2, 0)];
+        let sim = run(
+            ".....",
+            &[("walk 1", GridPos(0, 0), Direction::Right)],
+            &boxes,
+        );
+        assert_eq!(bot_positions(&sim), [GridPos(1, 0)]);
+        assert_eq!(box_positions(&sim), [GridPos(2, 0), GridPos(3, 0)]);
+    }
+
+    #[test]
+    fn push_row_of_boxes_into_wall() {
+        let boxes = [GridPos(1, 0), GridPos(2, 0)];
+        let sim = run(
+            "...#",
+            &[("walk 1", GridPos(0, 0), Direction::Right)],
+            &boxes,
+        );
+        assert_eq!(bot_positions(&sim), [GridPos(0, 0)]);
+        assert_eq!(box_positions(&sim), boxes);
+    }
+
+    #[test]
+    fn ramp_down_and_up() {
+        let sim = run("->.", &[("walk 2", GridPos(0, 0), Direction::Right)], &[]);
+        assert_eq!(bot_positions(&sim), [GridPos(2, 0)]);
+        let sim = run("->.", &[("walk 2", GridPos(2, 0), Direction::Left)], &[]);
+        assert_eq!(bot_positions(&sim), [GridPos(0, 0)]);
+    }
+
+    #[test]
+    fn ramp_entered_from_the_wrong_side() {
+        // the lower floor is to the right of the ramp, not to its left.
+        let sim = run(".>.", &[("walk 1", GridPos(0, 0), Direction::Right)], &[]);
+        assert_eq!(bot_positions(&sim), [GridPos(0, 0)]);
+        // ramps can't be left sideways.
+        let sim = run(
+            "-v-\n...",
+            &[("walk 1", GridPos(1, 0), Direction::Right)],
+            &[],
+        );
+        assert_eq!(bot_positions(&sim), [GridPos(1, 0)]);
+    }
+
+    #[test]
+    fn fall_into_void() {
+        let sim = run(". .", &[("walk 2", GridPos(0, 0), Direction::Right)], &[]);
+        assert_eq!(bot_positions(&sim), [GridPos(1, 0)]);
+        assert!(failure(&sim).contains("void"));
+    }
+
+    #[test]
+    fn missing_programs_halt() {
+        let mut level = Level::empty(String::from("test"));
+        level.bots.push((GridPos(0, 0), Direction::Right));
+        let program = asm::assemble("walk 1").unwrap();
+        let sim = Simulation::from_level(&level, &[program]);
+        assert_eq!(sim.bots.len(), 2);
+        assert_eq!(sim.bots[1].instructions, [Instruction::Halt.repr(); 32]);
+    }
+
+    #[test]
+    fn jump_past_end_of_memory() {
+        let mut program = [Instruction::Halt.repr(); 32];
+        program[..2].copy_from_slice(&[Instruction::Goto.repr(), 255]);
+        let map = Map::from_str("..o").unwrap();
+        let bots = [(program, GridPos(0, 0), Direction::Right)];
+        let mut sim = Simulation::new(map, BotCollision::Push, bots, []);
+        for _ in 0..3 {
+            sim.step();
+        }
+        assert!(failure(&sim).contains("halted"));
+    }
+
+    #[test]
+    fn head_on_collision() {
+        // walking onto the same tile.
+        let bots = [
+            ("walk 1", GridPos(0, 0), Direction::Right),
+            ("walk 1", GridPos(2, 0), Direction::Left),
+        ];
+        let sim = run("...", &bots, &[]);
+        assert_eq!(bot_positions(&sim), [GridPos(0, 0), GridPos(2, 0)]);
+
+        // walking into each other.
+        let bots = [
+            ("walk 1", GridPos(1, 0), Direction::Right),
+            ("walk 1", GridPos(2, 0), Direction::Left),
+        ];
+        let sim = run("....", &bots, &[]);
+        assert_eq!(bot_positions(&sim), [GridPos(1, 0), GridPos(2, 0)]);
+    }
+
+    #[test]
+    fn push_from_opposite_sides() {
+        let boxes = [GridPos(2, 0), GridPos(3, 0)];
+        let left = ("walk 1", GridPos(1, 0), Direction::Right);
+        let right = ("walk 1", GridPos(4, 0), Direction::Left);
+        for bots in [[left, right], [right, left]] {
+            let sim = run("......", &bots, &boxes);
+            let mut positions = bot_positions(&sim);
+            positions.sort_by_key(|pos| pos.0);
+            assert_eq!(positions, [GridPos(1, 0), GridPos(4, 0)]);
+            assert_eq!(box_positions(&sim), boxes);
+        }
+    }
+
+    #[test]
+    fn push_in_same_direction() {
+        let back = ("walk 1", GridPos(0, 0), Direction::Right);
+        let front = ("walk 1", GridPos(1, 0), Direction::Right);
+        for bots in [[back, front], [front, back]] {
+            let sim = run(".....", &bots, &[GridPos(2, 0)]);
+            let mut positions = bot_positions(&sim);
+            positions.sort_by_key(|pos| pos.0);
+            assert_eq!(positions, [GridPos(1, 0), GridPos(2, 0)]);
+            assert_eq!(box_positions(&sim), [GridPos(3, 0)]);
+        }
+    }
+}
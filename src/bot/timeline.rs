@@ -0,0 +1,41 @@
+use crate::bot::sim::{SimEntity, Simulation, Snapshot};
+use crate::draw;
+
+/// A snapshot of the simulation after every tick of the current run.
+pub struct Timeline {
+    snapshots: Vec<Snapshot>,
+    /// The render steps of each tick, one batch per bot.
+    steps: Vec<Vec<Vec<(SimEntity, draw::Step)>>>,
+}
+
+impl Timeline {
+    pub fn new(sim: &Simulation) -> Self {
+        Timeline {
+            snapshots: vec![sim.snapshot()],
+            steps: Vec::new(),
+        }
+    }
+
+    /// Records the state after the latest tick of `sim` and the steps of that tick,
+    /// dropping all ticks after it if it has been rewound.
+    pub fn record(&mut self, sim: &Simulation, steps: Vec<Vec<(SimEntity, draw::Step)>>) {
+        self.snapshots.truncate(sim.tick as usize);
+        self.snapshots.push(sim.snapshot());
+        self.steps.truncate(sim.tick as usize - 1);
+        self.steps.push(steps);
+    }
+
+    /// The last tick for which there is a snapshot.
+    pub fn last_tick(&self) -> u64 {
+        self.snapshots.len() as u64 - 1
+    }
+
+    pub fn get(&self, tick: u64) -> Option<&Snapshot> {
+        self.snapshots.get(tick as usize)
+    }
+
+    /// The steps of the first `ticks` ticks, as they were shown while running.
+    pub fn steps(&self, ticks: u64) -> &[Vec<Vec<(SimEntity, draw::Step)>>] {
+        &self.steps[..ticks as usize]
+    }
+}
@@ -1,7 +1,9 @@
 use std::collections::VecDeque;
+use std::fmt::{self, Display};
 use std::ops::Add;
 use std::ops::Deref;
 use std::ops::Mul;
+use std::time::Duration;
 
 use crate::bot::BotData;
 use crate::map::EntityKind;
@@ -12,6 +14,7 @@ use crate::map::Place;
 use crate::util::StateLocal;
 use crate::Direction;
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 mod mesh;
 
@@ -22,7 +25,7 @@ pub struct DrawUpdates {
     pub data: VecDeque<Vec<(Entity, Step)>>,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Step {
     Idle,
     Move(GridPos, GridPos),
@@ -39,7 +42,55 @@ impl DrawUpdates {
 }
 
 pub fn init_timer(world: &mut World) {
-    world.insert_resource(DrawTimer(Timer::from_seconds(0.5, false)));
+    world.insert_resource(DrawTimer(Timer::from_seconds(0.5, true)));
+}
+
+/// The available speeds as multiples of one step per half second,
+/// followed by running without any animation.
+const SPEEDS: [f32; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+
+/// How fast a program is run, an index into `SPEEDS`
+/// or `SPEEDS.len()` for instant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimSpeed(usize);
+
+impl Default for SimSpeed {
+    fn default() -> Self {
+        SimSpeed(2)
+    }
+}
+
+impl SimSpeed {
+    /// The number of ticks `progress_world` may simulate in a single frame.
+    pub const INSTANT_TICKS_PER_FRAME: usize = 200;
+
+    pub fn faster(&mut self) {
+        self.0 = (self.0 + 1).min(SPEEDS.len());
+    }
+
+    pub fn slower(&mut self) {
+        self.0 = self.0.saturating_sub(1);
+    }
+
+    pub fn is_instant(self) -> bool {
+        self.0 == SPEEDS.len()
+    }
+
+    /// How long each step is animated for, `None` if it isn't animated.
+    pub fn step_duration(self) -> Option<Duration> {
+        SPEEDS
+            .get(self.0)
+            .map(|speed| Duration::from_secs_f32(0.5 / speed))
+    }
+}
+
+impl Display for SimSpeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match SPEEDS.get(self.0) {
+            Some(speed) => write!(f, "{}x", speed),
+            None => f.write_str("instant"),
+        }
+    }
 }
 
 pub fn init_map_system(
@@ -50,143 +101,163 @@ pub fn init_map_system(
     entities: Query<(Entity, &EntityKind, &GridPos)>,
     robots: Query<&BotData>,
 ) {
+    commands.spawn_bundle(light_bundle()).insert(StateLocal);
     commands
-        .spawn_bundle(DirectionalLightBundle {
-            transform: Transform::from_xyz(1.7, 10.0, 2.0).looking_at(Vec3::ZERO, Vec3::Y),
-            ..Default::default()
-        })
-        .insert(StateLocal);
-    let viewing_pos = Vec3::new(7.0, 17.0, 10.0);
-    commands
-        .spawn_bundle(PerspectiveCameraBundle {
-            transform: Transform::from_translation(viewing_pos)
-                .looking_at(Vec3::ZERO, Vec3::Y)
-                .with_translation(
-                    viewing_pos
-                        + (Vec3::X * level.map.width as f32 * 0.7)
-                        + (Vec3::Y * level.map.height as f32 * 0.7),
-                ),
-            ..Default::default()
-        })
+        .spawn_bundle(camera_bundle(&level.map))
         .insert(StateLocal);
 
-    let box_xy = shape::Box {
-        min_x: -0.5,
-        max_x: 0.5,
-        min_y: 0.0,
-        max_y: 1.0,
-        min_z: -0.5,
-        max_z: 0.5,
-    };
-
     for x in 0..level.map.width {
         for y in 0..level.map.height {
-            let transform = Transform::from_xyz(
-                x as f32 - level.map.width as f32 / 2.0,
-                0.0,
-                y as f32 - level.map.height as f32 / 2.0,
-            );
-            match level.map.tile(GridPos(x, y)) {
-                Place::UpperFloor => {
-                    commands
-                        .spawn_bundle(PbrBundle {
-                            mesh: meshes.add(Mesh::from(shape::Box {
-                                max_y: UPPER_FLOOR,
-                                ..box_xy
-                            })),
-                            material: materials.add(Color::rgb(0.3, 0.5, 0.3).into()),
-                            transform,
-                            ..Default::default()
-                        })
-                        .insert(StateLocal);
-                }
-                Place::LowerFloor => {
-                    commands
-                        .spawn_bundle(PbrBundle {
-                            mesh: meshes.add(Mesh::from(shape::Box {
-                                max_y: LOWER_FLOOR,
-                                ..box_xy
-                            })),
-                            material: materials.add(Color::rgb(0.3, 0.5, 0.3).into()),
-                            transform,
-                            ..Default::default()
-                        })
-                        .insert(StateLocal);
-                }
-                Place::Ramp(dir) => {
-                    commands
-                        .spawn_bundle(PbrBundle {
-                            mesh: meshes.add(mesh::slope_mesh(dir)),
-                            material: materials.add(Color::rgb(0.3, 0.5, 0.3).into()),
-                            transform: transform
-                                .looking_at(transform.translation - Vec3::Y, Vec3::Z),
-                            ..Default::default()
-                        })
-                        .insert(StateLocal);
-                }
-                Place::Void => {}
-                Place::Wall => {
-                    commands
-                        .spawn_bundle(PbrBundle {
-                            mesh: meshes.add(Mesh::from(shape::Box {
-                                max_y: 0.9,
-                                ..box_xy
-                            })),
-                            material: materials.add(Color::rgb(0.7, 0.9, 0.7).into()),
-                            transform,
-                            ..Default::default()
-                        })
-                        .insert(StateLocal);
-                }
-                Place::Exit => {
-                    commands
-                        .spawn_bundle(PbrBundle {
-                            mesh: meshes.add(Mesh::from(shape::Box {
-                                max_y: 1.0,
-                                ..box_xy
-                            })),
-                            material: materials.add(Color::rgb(0.1, 0.9, 0.1).into()),
-                            transform,
-                            ..Default::default()
-                        })
-                        .insert(StateLocal);
-                }
+            if let Some(tile) = tile_bundle(&mut meshes, &mut materials, &level.map, GridPos(x, y))
+            {
+                commands.spawn_bundle(tile).insert(StateLocal);
             }
         }
     }
 
     for (entity, kind, &position) in entities.iter() {
-        let transform = Transform::from_translation(pos_to_world(&level.map, position));
-
-        match kind {
+        let bundle = match kind {
             EntityKind::Robot => {
                 let state = robots.get(entity).expect("bot without bot state");
-                commands.get_or_spawn(entity).insert_bundle(PbrBundle {
-                    mesh: meshes.add(mesh::robot_mesh()),
-                    material: materials.add(Color::rgb(0.25, 0.12, 0.1).into()),
-                    transform: transform.with_rotation(
-                        Quat::from_rotation_y(dir_to_radians(state.start_dir))
-                            * Quat::from_rotation_x(std::f32::consts::PI * 1.5),
-                    ),
-                    ..Default::default()
-                });
-            }
-            EntityKind::Box => {
-                commands.get_or_spawn(entity).insert_bundle(PbrBundle {
-                    mesh: meshes.add(Mesh::from(shape::Box {
-                        min_x: -0.4,
-                        max_x: 0.4,
-                        min_y: 0.0,
-                        max_y: 0.8,
-                        min_z: -0.4,
-                        max_z: 0.4,
-                    })),
-                    material: materials.add(Color::rgb(0.25, 0.12, 0.1).into()),
-                    transform,
-                    ..Default::default()
-                });
+                robot_bundle(
+                    &mut meshes,
+                    &mut materials,
+                    &level.map,
+                    position,
+                    state.start_dir,
+                )
             }
-        }
+            EntityKind::Box => box_bundle(&mut meshes, &mut materials, &level.map, position),
+        };
+        commands.get_or_spawn(entity).insert_bundle(bundle);
+    }
+}
+
+pub fn light_bundle() -> DirectionalLightBundle {
+    DirectionalLightBundle {
+        transform: Transform::from_xyz(1.7, 10.0, 2.0).looking_at(Vec3::ZERO, Vec3::Y),
+        ..Default::default()
+    }
+}
+
+/// A camera looking at the whole `map`.
+pub fn camera_bundle(map: &Map) -> PerspectiveCameraBundle {
+    let viewing_pos = Vec3::new(7.0, 17.0, 10.0);
+    PerspectiveCameraBundle {
+        transform: Transform::from_translation(viewing_pos)
+            .looking_at(Vec3::ZERO, Vec3::Y)
+            .with_translation(
+                viewing_pos
+                    + (Vec3::X * map.width as f32 * 0.7)
+                    + (Vec3::Y * map.height as f32 * 0.7),
+            ),
+        ..Default::default()
+    }
+}
+
+const BOX_XY: shape::Box = shape::Box {
+    min_x: -0.5,
+    max_x: 0.5,
+    min_y: 0.0,
+    max_y: 1.0,
+    min_z: -0.5,
+    max_z: 0.5,
+};
+
+/// The mesh of the tile at `pos`, `None` for `Place::Void`.
+pub fn tile_bundle(
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    map: &Map,
+    GridPos(x, y): GridPos,
+) -> Option<PbrBundle> {
+    let transform = Transform::from_xyz(
+        x as f32 - map.width as f32 / 2.0,
+        0.0,
+        y as f32 - map.height as f32 / 2.0,
+    );
+    Some(match map.tile(GridPos(x, y)) {
+        Place::UpperFloor => PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Box {
+                max_y: UPPER_FLOOR,
+                ..BOX_XY
+            })),
+            material: materials.add(Color::rgb(0.3, 0.5, 0.3).into()),
+            transform,
+            ..Default::default()
+        },
+        Place::LowerFloor => PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Box {
+                max_y: LOWER_FLOOR,
+                ..BOX_XY
+            })),
+            material: materials.add(Color::rgb(0.3, 0.5, 0.3).into()),
+            transform,
+            ..Default::default()
+        },
+        Place::Ramp(dir) => PbrBundle {
+            mesh: meshes.add(mesh::slope_mesh(dir)),
+            material: materials.add(Color::rgb(0.3, 0.5, 0.3).into()),
+            transform: transform.looking_at(transform.translation - Vec3::Y, Vec3::Z),
+            ..Default::default()
+        },
+        Place::Void => return None,
+        Place::Wall => PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Box {
+                max_y: 0.9,
+                ..BOX_XY
+            })),
+            material: materials.add(Color::rgb(0.7, 0.9, 0.7).into()),
+            transform,
+            ..Default::default()
+        },
+        Place::Exit => PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Box {
+                max_y: 1.0,
+                ..BOX_XY
+            })),
+            material: materials.add(Color::rgb(0.1, 0.9, 0.1).into()),
+            transform,
+            ..Default::default()
+        },
+    })
+}
+
+pub fn robot_bundle(
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    map: &Map,
+    pos: GridPos,
+    dir: Direction,
+) -> PbrBundle {
+    let mut transform = Transform::default();
+    place_entity(map, pos, Some(dir), &mut transform);
+    PbrBundle {
+        mesh: meshes.add(mesh::robot_mesh()),
+        material: materials.add(Color::rgb(0.25, 0.12, 0.1).into()),
+        transform,
+        ..Default::default()
+    }
+}
+
+pub fn box_bundle(
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    map: &Map,
+    pos: GridPos,
+) -> PbrBundle {
+    PbrBundle {
+        mesh: meshes.add(Mesh::from(shape::Box {
+            min_x: -0.4,
+            max_x: 0.4,
+            min_y: 0.0,
+            max_y: 0.8,
+            min_z: -0.4,
+            max_z: 0.4,
+        })),
+        material: materials.add(Color::rgb(0.25, 0.12, 0.1).into()),
+        transform: Transform::from_translation(pos_to_world(map, pos)),
+        ..Default::default()
     }
 }
 
@@ -202,21 +273,43 @@ pub fn update_map_system(
     mut events: ResMut<DrawUpdates>,
     time: Res<Time>,
     mut timer: ResMut<DrawTimer>,
+    speed: Res<SimSpeed>,
     level: Res<Level>,
     mut transforms: Query<&mut Transform>,
 ) {
-    timer.0.tick(time.delta());
-    if timer.0.finished() {
-        events.data.pop_front();
-        timer.0 = Timer::from_seconds(0.5, false);
+    if events.data.is_empty() {
+        // start animating the next step from its beginning.
+        timer.0.reset();
+        return;
     }
 
-    let steps = if let Some(steps) = events.data.front() {
-        steps
-    } else {
-        return;
+    // steps which are already finished are moved to their end
+    // directly, so that multiple steps can finish in one frame.
+    let finished = match speed.step_duration() {
+        Some(duration) => {
+            timer.0.set_duration(duration);
+            timer.0.tick(time.delta());
+            timer.0.times_finished() as usize
+        }
+        None => events.data.len(),
     };
+    for _ in 0..finished {
+        if let Some(steps) = events.data.pop_front() {
+            animate_steps(&steps, 1.0, &level, &mut transforms);
+        }
+    }
+
+    if let Some(steps) = events.data.front() {
+        animate_steps(steps, timer.percent(), &level, &mut transforms);
+    }
+}
 
+fn animate_steps(
+    steps: &[(Entity, Step)],
+    percent: f32,
+    level: &Level,
+    transforms: &mut Query<&mut Transform>,
+) {
     for &(entity, step) in steps {
         match step {
             Step::Idle => {}
@@ -224,7 +317,7 @@ pub fn update_map_system(
                 let mut transform = transforms.get_mut(entity).expect("sus step");
                 let old_pos = pos_to_world(&level.map, from);
                 let new_pos = pos_to_world(&level.map, to);
-                let position = interpolate(timer.percent(), old_pos, new_pos);
+                let position = interpolate(percent, old_pos, new_pos);
                 *transform = transform.with_translation(position);
             }
             Step::MoveFail => {}
@@ -237,15 +330,24 @@ pub fn update_map_system(
                 let new_rot = Quat::from_rotation_y(new_rad)
                     * Quat::from_rotation_x(dir_to_radians(Direction::Right));
                 if old_rot.dot(new_rot) < 0. {
-                    transform.rotation = (-old_rot).slerp(new_rot, timer.percent());
+                    transform.rotation = (-old_rot).slerp(new_rot, percent);
                 } else {
-                    transform.rotation = old_rot.slerp(new_rot, timer.percent());
+                    transform.rotation = old_rot.slerp(new_rot, percent);
                 }
             }
         }
     }
 }
 
+/// Moves `transform` to `pos` without any animation, facing `dir` if it is a bot.
+pub fn place_entity(map: &Map, pos: GridPos, dir: Option<Direction>, transform: &mut Transform) {
+    transform.translation = pos_to_world(map, pos);
+    if let Some(dir) = dir {
+        transform.rotation = Quat::from_rotation_y(dir_to_radians(dir))
+            * Quat::from_rotation_x(dir_to_radians(Direction::Right));
+    }
+}
+
 fn pos_to_world(map: &Map, GridPos(x, y): GridPos) -> Vec3 {
     let height = match map.tile(GridPos(x, y)) {
         Place::UpperFloor => UPPER_FLOOR,
@@ -253,7 +355,7 @@ fn pos_to_world(map: &Map, GridPos(x, y): GridPos) -> Vec3 {
         Place::Ramp(_) => (UPPER_FLOOR + LOWER_FLOOR) / 2.0,
         Place::Void => -1.0,
         Place::Exit => LOWER_FLOOR,
-        _ => todo!(),
+        Place::Wall => 0.9,
     };
 
     Vec3::new(
@@ -263,6 +365,17 @@ fn pos_to_world(map: &Map, GridPos(x, y): GridPos) -> Vec3 {
     )
 }
 
+/// The position of the tile below `world`, the inverse of `pos_to_world`.
+pub fn world_to_pos(map: &Map, world: Vec3) -> Option<GridPos> {
+    let x = (world.x + map.width as f32 / 2.0).round();
+    let y = (world.z + map.height as f32 / 2.0).round();
+    if x >= 0.0 && y >= 0.0 && (x as usize) < map.width && (y as usize) < map.height {
+        Some(GridPos(x as usize, y as usize))
+    } else {
+        None
+    }
+}
+
 fn dir_to_radians(dir: Direction) -> f32 {
     match dir {
         Direction::Up => 0.0,
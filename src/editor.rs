@@ -0,0 +1,484 @@
+use std::path::Path;
+
+use crate::draw;
+use crate::level_loader;
+use crate::map::{EntityKind, GridPos, Level, LevelList, Place};
+use crate::util::StateLocal;
+use crate::{CurrentLevel, Direction, GameState};
+use bevy::prelude::*;
+
+const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
+const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
+const SELECTED_TOOL: Color = Color::rgb(0.1, 0.5, 0.1);
+const VOID_TILE: Color = Color::rgb(0.05, 0.05, 0.05);
+
+/// The height at which clicks are projected onto the map,
+/// between the lower and the upper floor.
+const PICK_HEIGHT: f32 = 0.35;
+
+/// What clicking on a tile does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Place(Place),
+    /// Adds or removes a box.
+    Box,
+    /// Adds a bot facing `LevelEditor::dir`, removing it if it already faces that way.
+    Bot,
+}
+
+const TOOLS: [(Tool, &str); 11] = [
+    (Tool::Place(Place::LowerFloor), "lower floor"),
+    (Tool::Place(Place::UpperFloor), "upper floor"),
+    (Tool::Place(Place::Ramp(Direction::Up)), "ramp up"),
+    (Tool::Place(Place::Ramp(Direction::Down)), "ramp down"),
+    (Tool::Place(Place::Ramp(Direction::Left)), "ramp left"),
+    (Tool::Place(Place::Ramp(Direction::Right)), "ramp right"),
+    (Tool::Place(Place::Wall), "wall"),
+    (Tool::Place(Place::Void), "void"),
+    (Tool::Place(Place::Exit), "exit"),
+    (Tool::Box, "box"),
+    (Tool::Bot, "bot"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Select(Tool),
+    /// Rotates the start direction of new bots clockwise.
+    Rotate,
+    Resize(isize, isize),
+    New,
+    Save,
+    TestPlay,
+}
+
+#[derive(Component)]
+pub struct EditorButton(Action);
+
+/// Marks everything which is redrawn after changing the level.
+#[derive(Component)]
+pub struct EditorView;
+
+#[derive(Component)]
+pub struct EditorStatus;
+
+/// The level being edited.
+pub struct LevelEditor {
+    pub level: Level,
+    pub tool: Tool,
+    /// The start direction of new bots.
+    pub dir: Direction,
+    pub status: String,
+    changed: bool,
+}
+
+impl LevelEditor {
+    pub fn new(level: Level) -> Self {
+        LevelEditor {
+            level,
+            tool: Tool::Place(Place::LowerFloor),
+            dir: Direction::Right,
+            status: String::new(),
+            changed: true,
+        }
+    }
+
+    /// Applies the current tool to the tile at `pos`.
+    pub fn apply(&mut self, pos: GridPos) {
+        let level = &mut self.level;
+        match self.tool {
+            Tool::Place(place) => {
+                level.map.set_tile(pos, place);
+                // bots and boxes can't start on these tiles.
+                if matches!(place, Place::Wall | Place::Void | Place::Exit) {
+                    level.boxes.retain(|&b| b != pos);
+                    level.bots.retain(|&(bot, _)| bot != pos);
+                }
+            }
+            Tool::Box => {
+                if let Some(i) = level.boxes.iter().position(|&b| b == pos) {
+                    level.boxes.remove(i);
+                } else {
+                    level.bots.retain(|&(bot, _)| bot != pos);
+                    level.boxes.push(pos);
+                }
+            }
+            Tool::Bot => match level.bots.iter().position(|&(bot, _)| bot == pos) {
+                Some(i) if level.bots[i].1 == self.dir => {
+                    level.bots.remove(i);
+                }
+                Some(i) => level.bots[i].1 = self.dir,
+                None => {
+                    level.boxes.retain(|&b| b != pos);
+                    level.bots.push((pos, self.dir));
+                }
+            },
+        }
+        self.changed = true;
+    }
+
+    /// Grows or shrinks the map, removing all boxes and bots which end up outside of it.
+    pub fn resize(&mut self, width: isize, height: isize) {
+        let level = &mut self.level;
+        let width = (level.map.width as isize + width).max(1) as usize;
+        let height = (level.map.height as isize + height).max(1) as usize;
+        level.map.resize(width, height);
+        level.boxes.retain(|&GridPos(x, y)| x < width && y < height);
+        level
+            .bots
+            .retain(|&(GridPos(x, y), _)| x < width && y < height);
+        self.changed = true;
+    }
+}
+
+/// An empty level whose name isn't used by any level in `levels`.
+fn new_level(levels: &LevelList) -> Level {
+    let name = (1..)
+        .map(|i| format!("level-{}", i))
+        .find(|name| levels.levels.iter().all(|l| &l.name != name))
+        .unwrap();
+    Level::empty(name)
+}
+
+/// Replaces the level with the same name in `levels`, or adds `level`
+/// if there is none, returning its index.
+fn store_in_list(levels: &mut LevelList, level: &Level) -> usize {
+    match levels.levels.iter().position(|l| l.name == level.name) {
+        Some(i) => {
+            levels.levels[i] = level.clone();
+            i
+        }
+        None => levels.add_level(level.clone()),
+    }
+}
+
+fn spawn_button(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    position: Rect<Val>,
+    msg: &str,
+    action: Action,
+) {
+    commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(150.0), Val::Px(35.0)),
+                position_type: PositionType::Absolute,
+                margin: Rect::all(Val::Auto),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                position,
+                ..Default::default()
+            },
+            color: NORMAL_BUTTON.into(),
+            ..Default::default()
+        })
+        .insert(EditorButton(action))
+        .insert(StateLocal)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    msg,
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 25.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+        });
+}
+
+pub fn init(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    level: Res<Level>,
+    map_entities: Query<Entity, With<EntityKind>>,
+) {
+    // the bots and boxes of the last level would otherwise still be drawn.
+    for e in map_entities.iter() {
+        commands.entity(e).despawn();
+    }
+    commands.insert_resource(LevelEditor::new(level.clone()));
+
+    for (i, &(tool, msg)) in TOOLS.iter().enumerate() {
+        let position = Rect {
+            left: Val::Percent(1.0),
+            right: Val::Auto,
+            top: Val::Percent(1.0 + i as f32 * 6.0),
+            bottom: Val::Auto,
+        };
+        spawn_button(
+            &mut commands,
+            &asset_server,
+            position,
+            msg,
+            Action::Select(tool),
+        );
+    }
+
+    let actions = [
+        ("", Action::Rotate),
+        ("wider", Action::Resize(1, 0)),
+        ("narrower", Action::Resize(-1, 0)),
+        ("taller", Action::Resize(0, 1)),
+        ("shorter", Action::Resize(0, -1)),
+        ("new", Action::New),
+        ("save", Action::Save),
+        ("test play", Action::TestPlay),
+    ];
+    for (i, (msg, action)) in actions.into_iter().enumerate() {
+        let position = Rect {
+            left: Val::Auto,
+            right: Val::Percent(1.0),
+            top: Val::Percent(1.0 + i as f32 * 6.0),
+            bottom: Val::Auto,
+        };
+        spawn_button(&mut commands, &asset_server, position, msg, action);
+    }
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Percent(20.0),
+                    right: Val::Auto,
+                    top: Val::Auto,
+                    bottom: Val::Percent(2.0),
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 25.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(EditorStatus)
+        .insert(StateLocal);
+}
+
+/// Draws the edited level again after it changed.
+pub fn redraw(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut editor: ResMut<LevelEditor>,
+    view: Query<Entity, With<EditorView>>,
+) {
+    if !editor.changed {
+        return;
+    }
+    editor.changed = false;
+
+    for e in view.iter() {
+        commands.entity(e).despawn();
+    }
+
+    let level = &editor.level;
+    commands
+        .spawn_bundle(draw::light_bundle())
+        .insert(EditorView)
+        .insert(StateLocal);
+    commands
+        .spawn_bundle(draw::camera_bundle(&level.map))
+        .insert(EditorView)
+        .insert(StateLocal);
+
+    let void_mesh = meshes.add(Mesh::from(shape::Box {
+        min_x: -0.45,
+        max_x: 0.45,
+        min_y: -0.02,
+        max_y: 0.0,
+        min_z: -0.45,
+        max_z: 0.45,
+    }));
+    let void_material = materials.add(VOID_TILE.into());
+    for x in 0..level.map.width {
+        for y in 0..level.map.height {
+            let pos = GridPos(x, y);
+            let bundle = draw::tile_bundle(&mut meshes, &mut materials, &level.map, pos)
+                .unwrap_or_else(|| {
+                    // void tiles are drawn as a dark outline so that they can be clicked.
+                    let mut transform = Transform::default();
+                    draw::place_entity(&level.map, pos, None, &mut transform);
+                    transform.translation.y = 0.0;
+                    PbrBundle {
+                        mesh: void_mesh.clone(),
+                        material: void_material.clone(),
+                        transform,
+                        ..Default::default()
+                    }
+                });
+            commands
+                .spawn_bundle(bundle)
+                .insert(EditorView)
+                .insert(StateLocal);
+        }
+    }
+
+    for &(pos, dir) in &level.bots {
+        commands
+            .spawn_bundle(draw::robot_bundle(
+                &mut meshes,
+                &mut materials,
+                &level.map,
+                pos,
+                dir,
+            ))
+            .insert(EditorView)
+            .insert(StateLocal);
+    }
+    for &pos in &level.boxes {
+        commands
+            .spawn_bundle(draw::box_bundle(
+                &mut meshes,
+                &mut materials,
+                &level.map,
+                pos,
+            ))
+            .insert(EditorView)
+            .insert(StateLocal);
+    }
+}
+
+pub fn update_buttons(
+    mut state: ResMut<State<GameState>>,
+    mut editor: ResMut<LevelEditor>,
+    mut levels: ResMut<LevelList>,
+    mut level: ResMut<Level>,
+    mut current_level: ResMut<CurrentLevel>,
+    interaction_query: Query<(&EditorButton, &Interaction), Changed<Interaction>>,
+) {
+    for (button, interaction) in interaction_query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        match button.0 {
+            Action::Select(tool) => editor.tool = tool,
+            Action::Rotate => {
+                editor.dir = match editor.dir {
+                    Direction::Up => Direction::Right,
+                    Direction::Right => Direction::Down,
+                    Direction::Down => Direction::Left,
+                    Direction::Left => Direction::Up,
+                };
+            }
+            Action::Resize(width, height) => editor.resize(width, height),
+            Action::New => {
+                editor.level = new_level(&levels);
+                editor.changed = true;
+                editor.status = format!("created the level `{}`", editor.level.name);
+            }
+            Action::Save => {
+                let pack = levels
+                    .levels
+                    .iter()
+                    .position(|l| l.name == editor.level.name)
+                    .and_then(|i| levels.pack_of(i));
+                if let Some(pack) = pack {
+                    editor.status = format!("levels of the pack `{}` can't be saved", pack.title);
+                    continue;
+                }
+                if let Err(e) = editor.level.validate() {
+                    editor.status = format!("can't save the level: {}", e.msg);
+                    continue;
+                }
+                editor.status = match level_loader::save_level(Path::new("assets"), &editor.level) {
+                    Ok(()) => {
+                        store_in_list(&mut levels, &editor.level);
+                        format!("saved the level `{}`", editor.level.name)
+                    }
+                    Err(e) => format!("{:#}", e),
+                };
+            }
+            Action::TestPlay => {
+                if let Err(e) = editor.level.validate() {
+                    editor.status = format!("can't play the level: {}", e.msg);
+                    continue;
+                }
+                current_level.0 = store_in_list(&mut levels, &editor.level);
+                *level = editor.level.clone();
+                state.set(GameState::ChangeLevel).unwrap();
+            }
+        }
+    }
+}
+
+/// Applies the current tool to the tile below the cursor.
+pub fn click_tile(
+    windows: Res<Windows>,
+    mouse: Res<Input<MouseButton>>,
+    mut editor: ResMut<LevelEditor>,
+    cameras: Query<(&Camera, &GlobalTransform), With<EditorView>>,
+    buttons: Query<&Interaction, With<Button>>,
+) {
+    if !mouse.just_pressed(MouseButton::Left) || buttons.iter().any(|&i| i != Interaction::None) {
+        return;
+    }
+
+    let window = if let Some(window) = windows.get_primary() {
+        window
+    } else {
+        return;
+    };
+    let (cursor, (camera, camera_transform)) =
+        match (window.cursor_position(), cameras.iter().next()) {
+            (Some(cursor), Some(camera)) => (cursor, camera),
+            _ => return,
+        };
+
+    // cast a ray from the cursor into the scene.
+    let size = Vec2::new(window.width(), window.height());
+    let ndc = cursor / size * 2.0 - Vec2::ONE;
+    let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix.inverse();
+    let near = ndc_to_world.project_point3(ndc.extend(1.0));
+    let far = ndc_to_world.project_point3(ndc.extend(0.5));
+    let ray = far - near;
+    if ray.y.abs() < f32::EPSILON {
+        return;
+    }
+    let hit = near + ray * ((PICK_HEIGHT - near.y) / ray.y);
+
+    if let Some(pos) = draw::world_to_pos(&editor.level.map, hit) {
+        editor.apply(pos);
+    }
+}
+
+pub fn update_ui(
+    editor: Res<LevelEditor>,
+    mut buttons: Query<(&EditorButton, &Interaction, &Children, &mut UiColor)>,
+    mut status: Query<&mut Text, With<EditorStatus>>,
+    mut text: Query<&mut Text, Without<EditorStatus>>,
+) {
+    for (button, interaction, children, mut color) in buttons.iter_mut() {
+        *color = match (button.0, interaction) {
+            (Action::Select(tool), _) if tool == editor.tool => SELECTED_TOOL.into(),
+            (_, Interaction::Hovered) => HOVERED_BUTTON.into(),
+            _ => NORMAL_BUTTON.into(),
+        };
+        if button.0 == Action::Rotate {
+            text.get_mut(children[0]).unwrap().sections[0].value =
+                format!("bot: {:?}", editor.dir).to_lowercase();
+        }
+    }
+
+    let level = &editor.level;
+    let mut status = status.single_mut();
+    status.sections[0].value = format!(
+        "{} ({}x{}){}{}",
+        level.name,
+        level.map.width,
+        level.map.height,
+        if editor.status.is_empty() { "" } else { ": " },
+        editor.status,
+    );
+}
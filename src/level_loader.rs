@@ -1,9 +1,52 @@
+use std::path::{Path, PathBuf};
+
 use crate::{
-    map::{Level, LevelList, LevelSerde},
+    map::{Level, LevelEntry, LevelError, LevelList, LevelPack},
+    save::SaveGame,
     CurrentLevel, GameState,
 };
-use bevy::prelude::*;
+use anyhow::Context;
+use bevy::asset::LoadState;
+use bevy::{prelude::*, reflect::TypeUuid};
 use bevy_asset::{AssetLoader, AssetServer};
+use serde::Deserialize;
+
+/// The contents of a `.level` or `.map` file.
+///
+/// These are separate assets so that changes to them are noticed.
+#[derive(Debug, TypeUuid)]
+#[uuid = "1c5a4a49-5d0e-4b83-9c33-0a5bd2a6c8f1"]
+pub struct LevelSource(pub String);
+
+#[derive(Default)]
+pub struct LevelSourceLoader;
+
+impl AssetLoader for LevelSourceLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy_asset::LoadContext,
+    ) -> bevy_asset::BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            // invalid characters are reported when parsing the level.
+            let src = String::from_utf8_lossy(bytes).into_owned();
+            load_context.set_default_asset(bevy_asset::LoadedAsset::new(LevelSource(src)));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level", "map"]
+    }
+}
+
+/// `levels.json`, which depends on the files of all its levels.
+#[derive(Debug, TypeUuid)]
+#[uuid = "8ffabd85-acf4-40ba-a784-c82a10ccd488"]
+pub struct LevelIndex {
+    pub entries: Result<Vec<LevelEntry>, LevelError>,
+    pub sources: Vec<Handle<LevelSource>>,
+}
 
 #[derive(Default)]
 pub struct LevelListLoader;
@@ -15,16 +58,23 @@ impl AssetLoader for LevelListLoader {
         load_context: &'a mut bevy_asset::LoadContext,
     ) -> bevy_asset::BoxedFuture<'a, Result<(), anyhow::Error>> {
         Box::pin(async move {
-            let level_list: Vec<LevelSerde> = serde_json::de::from_slice(&bytes).unwrap();
-            let beaten = vec![false; level_list.len()];
-            let mut levels = vec![];
-            for level in level_list.into_iter() {
-                levels.push(Level::from_level_serde(level).await);
-            }
-            load_context.set_default_asset(bevy_asset::LoadedAsset::new(crate::map::LevelList {
-                levels,
-                beaten,
-            }));
+            let entries = serde_json::de::from_slice::<Vec<LevelEntry>>(bytes)
+                .map_err(|e| LevelError::from_json("levels.json", e));
+            let paths = entries
+                .iter()
+                .flatten()
+                .map(|entry| entry.path())
+                .collect::<Vec<_>>();
+            let sources = paths
+                .iter()
+                .map(|path| load_context.get_handle(path.as_str()))
+                .collect();
+
+            let mut asset = bevy_asset::LoadedAsset::new(LevelIndex { entries, sources });
+            for path in &paths {
+                asset = asset.with_dependency(path.as_str().into());
+            }
+            load_context.set_default_asset(asset);
             Ok(())
         })
     }
@@ -35,20 +85,318 @@ impl AssetLoader for LevelListLoader {
 }
 
 pub fn start_load_level_assets(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let handle = asset_server.load::<LevelList, _>("./levels.json");
+    if let Err(e) = asset_server.watch_for_changes() {
+        eprintln!("levels won't be reloaded after changing them: {}", e);
+    }
+    // the path has to be the same one used when reloading it after a change.
+    let handle = asset_server.load::<LevelIndex, _>("levels.json");
     commands.insert_resource(handle);
 }
 
+/// The manifest `pack.json` of a level pack.
+#[derive(Deserialize)]
+struct PackManifest {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    author: String,
+    /// The levels of the pack, in the same format as `levels.json`.
+    levels: Vec<LevelEntry>,
+}
+
+/// The folder containing the level packs of the user, one directory each.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn packs_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("shitty-bot-game").join("levels"))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn packs_dir() -> Option<PathBuf> {
+    None
+}
+
+/// Loads the pack in `dir`, adding its levels to `levels` and `broken`.
+///
+/// Levels are named `<pack>/<level>` so that they don't clash with
+/// the shipped levels or those of other packs.
+fn load_pack(
+    dir: &Path,
+    levels: &mut Vec<Level>,
+    broken: &mut Vec<LevelError>,
+) -> Result<LevelPack, LevelError> {
+    let name = dir.file_name().unwrap().to_string_lossy().into_owned();
+    let manifest_path = dir.join("pack.json");
+    let file = manifest_path.display().to_string();
+    let bytes = std::fs::read(&manifest_path)
+        .map_err(|e| LevelError::new(&file, format!("failed to read the file: {}", e)))?;
+    let manifest: PackManifest =
+        serde_json::from_slice(&bytes).map_err(|e| LevelError::from_json(&file, e))?;
+
+    let start = levels.len();
+    for entry in manifest.levels {
+        let path = dir.join(entry.path());
+        let level = match std::fs::read_to_string(&path) {
+            Ok(src) => Level::from_entry(entry, &src),
+            Err(e) => Err(LevelError::new(
+                entry.path(),
+                format!("failed to read the file: {}", e),
+            )),
+        };
+        match level {
+            Ok(mut level) => {
+                level.name = format!("{}/{}", name, level.name);
+                for required in &mut level.meta.requires {
+                    *required = format!("{}/{}", name, required);
+                }
+                levels.push(level);
+            }
+            Err(mut e) => {
+                e.file = dir.join(&e.file).display().to_string();
+                broken.push(e);
+            }
+        }
+    }
+
+    Ok(LevelPack {
+        title: if manifest.title.is_empty() {
+            name.clone()
+        } else {
+            manifest.title
+        },
+        author: manifest.author,
+        name,
+        levels: start..levels.len(),
+    })
+}
+
+/// Loads all packs in the user levels folder, ignoring directories without a manifest.
+fn load_packs(levels: &mut Vec<Level>, broken: &mut Vec<LevelError>) -> Vec<LevelPack> {
+    let entries = match packs_dir().map(std::fs::read_dir) {
+        Some(Ok(entries)) => entries,
+        _ => return Vec::new(),
+    };
+    let mut dirs = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.join("pack.json").is_file())
+        .collect::<Vec<_>>();
+    dirs.sort();
+
+    let mut packs = Vec::new();
+    for dir in dirs {
+        match load_pack(&dir, levels, broken) {
+            Ok(pack) => packs.push(pack),
+            Err(e) => broken.push(e),
+        }
+    }
+    packs
+}
+
+/// Parses all levels of `index` followed by the level packs,
+/// marking levels as broken if their file couldn't be loaded.
+fn build_level_list(
+    index: &LevelIndex,
+    sources: &Assets<LevelSource>,
+    save: &SaveGame,
+) -> LevelList {
+    let mut levels = Vec::new();
+    let mut broken = Vec::new();
+    match &index.entries {
+        Ok(entries) => {
+            for (entry, source) in entries.iter().zip(&index.sources) {
+                let level = match sources.get(source) {
+                    Some(src) => Level::from_entry(entry.clone(), &src.0),
+                    None => Err(LevelError::new(entry.path(), "failed to read the file")),
+                };
+                match level {
+                    Ok(level) => levels.push(level),
+                    Err(e) => broken.push(e),
+                }
+            }
+        }
+        Err(e) => broken.push(e.clone()),
+    }
+    let packs = load_packs(&mut levels, &mut broken);
+    for level in &levels {
+        for required in &level.meta.requires {
+            if levels.iter().all(|l| &l.name != required) {
+                eprintln!("`{}` requires the unknown level `{}`", level.name, required);
+            }
+        }
+    }
+
+    LevelList {
+        beaten: levels.iter().map(|l| save.is_beaten(&l.name)).collect(),
+        levels,
+        broken,
+        packs,
+    }
+}
+
 pub fn setup_level_resources(
     mut commands: Commands,
     mut state: ResMut<State<GameState>>,
-    handle: Res<Handle<LevelList>>,
-    assets: Res<Assets<LevelList>>,
+    asset_server: Res<AssetServer>,
+    handle: Res<Handle<LevelIndex>>,
+    indices: Res<Assets<LevelIndex>>,
+    sources: Res<Assets<LevelSource>>,
 ) {
-    if let Some(levels) = assets.get(&*handle) {
-        state.set(GameState::StartScreen).unwrap();
-        commands.insert_resource(levels.clone());
-        commands.insert_resource(levels.levels[0].clone());
-        commands.insert_resource(CurrentLevel(0));
+    let index = if let Some(index) = indices.get(&*handle) {
+        index
+    } else {
+        return;
+    };
+    let load_state = asset_server.get_group_load_state(index.sources.iter().map(|h| h.id));
+    if let LoadState::NotLoaded | LoadState::Loading = load_state {
+        return;
+    }
+
+    state.set(GameState::StartScreen).unwrap();
+    let save = SaveGame::load();
+    let levels = build_level_list(index, &sources, &save);
+    for err in &levels.broken {
+        eprintln!("broken level {}", err);
+    }
+    let first = levels.levels.first().cloned();
+    commands.insert_resource(first.unwrap_or_else(|| Level::empty(String::from("level-1"))));
+    commands.insert_resource(levels);
+    commands.insert_resource(save);
+    commands.insert_resource(CurrentLevel(0));
+}
+
+/// Rebuilds the `LevelList` after `levels.json` or a level file changed,
+/// switching to the new version of the current level while keeping its programs.
+pub fn reload_levels(
+    mut index_events: EventReader<AssetEvent<LevelIndex>>,
+    mut source_events: EventReader<AssetEvent<LevelSource>>,
+    mut state: ResMut<State<GameState>>,
+    handle: Res<Handle<LevelIndex>>,
+    indices: Res<Assets<LevelIndex>>,
+    sources: Res<Assets<LevelSource>>,
+    levels: Option<ResMut<LevelList>>,
+    level: Option<ResMut<Level>>,
+    current_level: Option<ResMut<CurrentLevel>>,
+    save: Option<Res<SaveGame>>,
+) {
+    let changed = index_events.iter().count() + source_events.iter().count() > 0;
+    let (mut levels, mut level, mut current_level, save, index) =
+        match (levels, level, current_level, save, indices.get(&*handle)) {
+            (Some(levels), Some(level), Some(current), Some(save), Some(index)) if changed => {
+                (levels, level, current, save, index)
+            }
+            // still loading, which `setup_level_resources` takes care of.
+            _ => return,
+        };
+
+    *levels = build_level_list(index, &sources, &save);
+    for err in &levels.broken {
+        eprintln!("broken level {}", err);
+    }
+
+    let new_level = levels.levels.iter().position(|l| l.name == level.name);
+    match state.current() {
+        GameState::Programming | GameState::Running | GameState::Replay => match new_level {
+            Some(i) if levels.levels[i] == *level => current_level.0 = i,
+            Some(i) => {
+                current_level.0 = i;
+                state.overwrite_set(GameState::ChangeLevel).unwrap();
+            }
+            None => state.overwrite_set(GameState::StartScreen).unwrap(),
+        },
+        GameState::StartScreen => {
+            if let Some(i) = new_level {
+                current_level.0 = i;
+                *level = levels.levels[i].clone();
+            }
+        }
+        GameState::LoadScreen | GameState::ChangeLevel | GameState::LevelEditor => {}
+    }
+}
+
+fn read_level_list(assets: &Path) -> anyhow::Result<Vec<LevelEntry>> {
+    let path = assets.join("levels.json");
+    let bytes = std::fs::read(&path).with_context(|| format!("failed to read {:?}", path))?;
+    serde_json::de::from_slice(&bytes).with_context(|| format!("failed to parse {:?}", path))
+}
+
+/// Synchronously loads `levels.json` and its levels from the `assets` directory,
+/// bypassing the asset server.
+pub fn load_levels(assets: &Path) -> anyhow::Result<Vec<Level>> {
+    read_level_list(assets)?
+        .into_iter()
+        .map(|entry| {
+            let path = assets.join(entry.path());
+            let src = std::fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {:?}", path))?;
+            Ok(Level::from_entry(entry, &src)?)
+        })
+        .collect()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_level_list(assets: &Path, level_list: &[LevelEntry]) -> anyhow::Result<()> {
+    let path = assets.join("levels.json");
+    std::fs::write(&path, serde_json::to_string_pretty(level_list)? + "\n")
+        .with_context(|| format!("failed to write {:?}", path))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_level_file(assets: &Path, level: &Level) -> anyhow::Result<()> {
+    let path = assets.join(LevelEntry::File(level.name.clone()).path());
+    std::fs::write(
+        &path,
+        serde_json::to_string_pretty(&level.to_file())? + "\n",
+    )
+    .with_context(|| format!("failed to write {:?}", path))
+}
+
+/// Writes `level` to its `.level` file and updates its entry in `levels.json`,
+/// adding it at the end if there is no level with the same name.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_level(assets: &Path, level: &Level) -> anyhow::Result<()> {
+    write_level_file(assets, level)?;
+    let mut level_list = read_level_list(assets)?;
+    let entry = LevelEntry::File(level.name.clone());
+    match level_list.iter_mut().find(|e| e.name() == level.name) {
+        Some(old) => *old = entry,
+        None => level_list.push(entry),
+    }
+    write_level_list(assets, &level_list)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn save_level(_: &Path, _: &Level) -> anyhow::Result<()> {
+    anyhow::bail!("saving levels is not supported in the browser")
+}
+
+/// Converts all levels in the old format, which are split between `levels.json`
+/// and a `.map` file, to `.level` files, returning their names.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn migrate_levels(assets: &Path) -> anyhow::Result<Vec<String>> {
+    let mut level_list = read_level_list(assets)?;
+    let mut migrated = Vec::new();
+    for entry in level_list.iter_mut() {
+        if let LevelEntry::File(_) = entry {
+            continue;
+        }
+
+        let map_path = assets.join(entry.path());
+        let src = std::fs::read_to_string(&map_path)
+            .with_context(|| format!("failed to read {:?}", map_path))?;
+        let name = entry.name().to_string();
+        let old = std::mem::replace(entry, LevelEntry::File(name));
+        let level = Level::from_entry(old, &src)?;
+        migrated.push((level, map_path));
+    }
+
+    // only remove the old files once everything has been converted.
+    for (level, _) in &migrated {
+        write_level_file(assets, level)?;
+    }
+    write_level_list(assets, &level_list)?;
+    for (_, map_path) in &migrated {
+        std::fs::remove_file(map_path)
+            .with_context(|| format!("failed to remove {:?}", map_path))?;
     }
+    Ok(migrated.into_iter().map(|(level, _)| level.name).collect())
 }
@@ -0,0 +1,39 @@
+#![feature(let_chains)]
+#![feature(array_from_fn)]
+
+use serde::Deserialize;
+use serde::Serialize;
+
+pub mod bot;
+pub mod draw;
+pub mod editor;
+pub mod level_loader;
+pub mod map;
+pub mod replay;
+pub mod save;
+pub mod solver;
+pub mod start;
+pub mod ui;
+pub mod util;
+
+#[derive(Copy, Clone, Debug)]
+pub struct CurrentLevel(usize);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GameState {
+    LoadScreen,
+    StartScreen,
+    Programming,
+    Running,
+    ChangeLevel,
+    Replay,
+    LevelEditor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
@@ -1,44 +1,19 @@
-#![feature(let_chains)]
-#![feature(array_from_fn)]
-
 use bevy::prelude::*;
 
-mod bot;
-mod draw;
-mod level_loader;
-mod map;
-mod start;
-mod ui;
-mod util;
-
-use bot::BotData;
-use bot::BotState;
-use serde::Deserialize;
-use serde::Serialize;
-use ui::programming::StartButton;
-use ui::running::StopButton;
-
-use crate::bot::ExecutionFailure;
-
-#[derive(Copy, Clone, Debug)]
-pub struct CurrentLevel(usize);
-
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub enum GameState {
-    LoadScreen,
-    StartScreen,
-    Programming,
-    Running,
-    ChangeLevel,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-pub enum Direction {
-    Up,
-    Down,
-    Left,
-    Right,
-}
+use shitty_bot_game::bot::sim::Simulation;
+use shitty_bot_game::bot::timeline::Timeline;
+use shitty_bot_game::bot::{self, ExecutionFailure, SimEntities};
+use shitty_bot_game::replay::{self, ReplayPlayback};
+use shitty_bot_game::ui::manual::ManualButton;
+use shitty_bot_game::ui::programming::{
+    ClearButton, HintButton, LoadButton, SaveButton, StartButton, WatchButton,
+};
+use shitty_bot_game::ui::replay::StopReplayButton;
+use shitty_bot_game::ui::running::{
+    FasterButton, NextButton, NextLevelButton, PauseButton, SaveReplayButton, SlowerButton,
+    StopButton, TickButton,
+};
+use shitty_bot_game::{draw, editor, level_loader, start, ui, util, GameState};
 
 fn start_up_system(mut commands: Commands) {
     commands.spawn_bundle(UiCameraBundle::default());
@@ -47,13 +22,19 @@ fn start_up_system(mut commands: Commands) {
 fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
-        .add_asset::<map::LevelList>()
+        .add_asset::<level_loader::LevelIndex>()
+        .add_asset::<level_loader::LevelSource>()
         .init_asset_loader::<level_loader::LevelListLoader>()
+        .init_asset_loader::<level_loader::LevelSourceLoader>()
         .add_state(GameState::LoadScreen)
-        .insert_resource(bot::edit::InstructionsEditor::new())
+        .insert_resource(bot::edit::ProgramEditors::new(1))
+        .insert_resource(bot::debugger::Debugger::new(1))
         .insert_resource(draw::DrawUpdates::empty())
+        .insert_resource(draw::SimSpeed::default())
+        .insert_resource(ui::programming::HintsShown::default())
         .add_startup_system(level_loader::start_load_level_assets)
         .add_startup_system(start_up_system)
+        .add_system(level_loader::reload_levels)
         //
         .add_system_set(
             SystemSet::on_update(GameState::LoadScreen)
@@ -65,15 +46,19 @@ fn main() {
                 .with_system(ui::clear_mem.exclusive_system())
                 .with_system(start::init),
         )
-        .add_system_set(SystemSet::on_update(GameState::StartScreen).with_system(start::update))
         .add_system_set(
-            SystemSet::on_exit(GameState::StartScreen)
-                .with_system(util::delete_local_entities)
-                .with_system(ui::init),
+            SystemSet::on_update(GameState::StartScreen)
+                .with_system(start::update)
+                .with_system(start::refresh),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::StartScreen).with_system(util::delete_local_entities),
         )
         //
         .add_system_set(
             SystemSet::on_enter(GameState::ChangeLevel)
+                .with_system(ui::clear_mem.exclusive_system())
+                .with_system(ui::init)
                 .with_system(util::update_level_data.label("add_level"))
                 .with_system(util::spawn_map_entities.after("add_level")),
         )
@@ -81,7 +66,14 @@ fn main() {
         .add_system_set(
             SystemSet::on_enter(GameState::Programming)
                 .with_system(ui::add_button::<StartButton>)
-                .with_system(bot::init_state)
+                .with_system(ui::add_button::<SaveButton>)
+                .with_system(ui::add_button::<LoadButton>)
+                .with_system(ui::add_button::<ClearButton>)
+                .with_system(ui::add_button::<WatchButton>)
+                .with_system(ui::add_button::<ManualButton>)
+                .with_system(ui::add_button::<HintButton>)
+                .with_system(ui::programming::add_briefing)
+                .with_system(ui::add_bot_tabs)
                 .with_system(util::reset_bot_and_box_state.exclusive_system())
                 .with_system(ui::refresh_mem)
                 .with_system(draw::init_map_system),
@@ -89,28 +81,51 @@ fn main() {
         .add_system_set(
             SystemSet::on_update(GameState::Programming)
                 .with_system(ui::programming::update)
+                .with_system(ui::programming::update_hints)
+                .with_system(ui::programming::store_programs)
+                .with_system(ui::manual::toggle)
+                .with_system(ui::update_bot_tabs)
                 .with_system(util::to_start),
         )
         .add_system_set(
             SystemSet::on_exit(GameState::Programming)
                 .with_system(ui::programming::exit.label("exit"))
                 .with_system(util::delete_local_entities.after("exit"))
-                .with_system(ui::remove_button::<StartButton>.after("exit")),
+                .with_system(ui::remove_button::<StartButton>.after("exit"))
+                .with_system(ui::remove_button::<SaveButton>.after("exit"))
+                .with_system(ui::remove_button::<LoadButton>.after("exit"))
+                .with_system(ui::remove_button::<ClearButton>.after("exit"))
+                .with_system(ui::remove_button::<WatchButton>.after("exit"))
+                .with_system(ui::remove_button::<ManualButton>.after("exit"))
+                .with_system(ui::remove_button::<HintButton>.after("exit")),
         )
         //
         .add_system_set(
             SystemSet::on_enter(GameState::Running)
                 .with_system(draw::init_timer.exclusive_system())
+                .with_system(bot::init_simulation.exclusive_system())
                 .with_system(draw::init_map_system)
                 .with_system(ui::refresh_mem)
                 .with_system(ui::running::init)
-                .with_system(ui::add_button::<StopButton>),
+                .with_system(ui::add_button::<StopButton>)
+                .with_system(ui::add_button::<PauseButton>)
+                .with_system(ui::add_button::<TickButton>)
+                .with_system(ui::add_button::<NextButton>)
+                .with_system(ui::add_button::<SlowerButton>)
+                .with_system(ui::add_button::<FasterButton>)
+                .with_system(ui::add_button::<SaveReplayButton>)
+                .with_system(ui::running::add_timeline)
+                .with_system(ui::add_bot_tabs),
         )
         .add_system_set(
             SystemSet::on_update(GameState::Running)
                 .with_system(bot::progress_world.before("update_map_sys"))
                 .with_system(draw::update_map_system.label("update_map_sys"))
                 .with_system(ui::running::update1)
+                .with_system(ui::running::save_replay)
+                .with_system(ui::running::next_level)
+                .with_system(ui::running::update_timeline.before("update_map_sys"))
+                .with_system(ui::update_bot_tabs.before("refresh"))
                 .with_system(ui::refresh_mem.label("refresh"))
                 .with_system(ui::running::update2.after("refresh"))
                 .with_system(bot::level_complete_checker)
@@ -121,17 +136,67 @@ fn main() {
             SystemSet::on_exit(GameState::Running)
                 .with_system(util::delete_local_entities)
                 .with_system(ui::remove_button::<StopButton>)
+                .with_system(ui::remove_button::<PauseButton>)
+                .with_system(ui::remove_button::<TickButton>)
+                .with_system(ui::remove_button::<NextButton>)
+                .with_system(ui::remove_button::<SlowerButton>)
+                .with_system(ui::remove_button::<FasterButton>)
+                .with_system(ui::remove_button::<SaveReplayButton>)
+                .with_system(ui::remove_button::<NextLevelButton>)
+                .with_system(|mut draw_steps: ResMut<draw::DrawUpdates>| {
+                    draw_steps.data.clear();
+                })
+                .with_system(|mut commands: Commands| {
+                    commands.remove_resource::<Simulation>();
+                    commands.remove_resource::<SimEntities>();
+                    commands.remove_resource::<Timeline>();
+                    commands.remove_resource::<ExecutionFailure>();
+                }),
+        )
+        //
+        .add_system_set(
+            SystemSet::on_enter(GameState::Replay)
+                .with_system(draw::init_timer.exclusive_system())
+                .with_system(replay::init_playback.exclusive_system())
+                .with_system(draw::init_map_system)
+                .with_system(ui::replay::init)
+                .with_system(ui::add_button::<StopReplayButton>),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Replay)
+                .with_system(replay::progress_playback.before("update_map_sys"))
+                .with_system(draw::update_map_system.label("update_map_sys"))
+                .with_system(ui::replay::update)
+                .with_system(util::to_start),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::Replay)
+                .with_system(util::delete_local_entities)
+                .with_system(ui::remove_button::<StopReplayButton>)
                 .with_system(|mut draw_steps: ResMut<draw::DrawUpdates>| {
                     draw_steps.data.clear();
                 })
-                .with_system(
-                    |mut commands: Commands, query: Query<Entity, With<BotData>>| {
-                        for entity in query.iter() {
-                            commands.entity(entity).remove::<BotState>();
-                        }
-                        commands.remove_resource::<ExecutionFailure>();
-                    },
-                ),
+                .with_system(|mut commands: Commands| {
+                    commands.remove_resource::<ReplayPlayback>();
+                    commands.remove_resource::<SimEntities>();
+                }),
+        )
+        //
+        .add_system_set(SystemSet::on_enter(GameState::LevelEditor).with_system(editor::init))
+        .add_system_set(
+            SystemSet::on_update(GameState::LevelEditor)
+                .with_system(editor::update_buttons.label("edit"))
+                .with_system(editor::click_tile.label("edit"))
+                .with_system(editor::redraw.after("edit"))
+                .with_system(editor::update_ui.after("edit"))
+                .with_system(util::to_start),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::LevelEditor)
+                .with_system(util::delete_local_entities)
+                .with_system(|mut commands: Commands| {
+                    commands.remove_resource::<editor::LevelEditor>();
+                }),
         )
         //
         .run();
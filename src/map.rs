@@ -1,8 +1,11 @@
+use std::fmt::{self, Display};
+use std::ops::Range;
+
 use crate::Direction;
-use bevy::{prelude::*, reflect::TypeUuid};
+use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Place {
     UpperFloor,
     LowerFloor,
@@ -12,53 +15,446 @@ pub enum Place {
     Exit,
 }
 
-#[derive(Clone, Debug, TypeUuid)]
-#[uuid = "8ffabd85-acf4-40ba-a784-c82a10ccd488"]
+impl Place {
+    /// The tiles explained in the manual, with one ramp standing in for all directions.
+    pub const MANUAL: [Place; 6] = [
+        Place::LowerFloor,
+        Place::UpperFloor,
+        Place::Ramp(Direction::Up),
+        Place::Wall,
+        Place::Void,
+        Place::Exit,
+    ];
+
+    /// The name of this tile and what it does, as explained in the manual.
+    pub fn manual(self) -> (&'static str, &'static str) {
+        match self {
+            Place::LowerFloor => ("lower floor", "can be walked on"),
+            Place::UpperFloor => (
+                "upper floor",
+                "can be walked on, stepping down onto the lower floor is possible but not back up",
+            ),
+            Place::Ramp(_) => (
+                "ramp",
+                "connects the upper and the lower floor, but can only be entered and left \
+                along its slope",
+            ),
+            Place::Wall => ("wall", "blocks bots and boxes"),
+            Place::Void => ("void", "bots and boxes which fall into it are lost"),
+            Place::Exit => (
+                "exit",
+                "the level is won once all bots and boxes are on it, \
+                but a bot may only enter it after all boxes",
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct LevelList {
     pub levels: Vec<Level>,
     pub beaten: Vec<bool>,
+    /// The levels which could not be loaded.
+    pub broken: Vec<LevelError>,
+    /// The level packs of the user, whose levels come after the shipped ones.
+    pub packs: Vec<LevelPack>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// A directory of levels in the user levels folder.
+#[derive(Clone, Debug)]
+pub struct LevelPack {
+    /// The name of the directory, which prefixes the names of its levels.
+    pub name: String,
+    pub title: String,
+    pub author: String,
+    /// The indices of its levels in `LevelList::levels`.
+    pub levels: Range<usize>,
+}
+
+impl LevelList {
+    /// The number of levels which are not part of a pack.
+    pub fn shipped(&self) -> usize {
+        self.packs
+            .first()
+            .map_or(self.levels.len(), |pack| pack.levels.start)
+    }
+
+    pub fn pack_of(&self, level: usize) -> Option<&LevelPack> {
+        self.packs.iter().find(|pack| pack.levels.contains(&level))
+    }
+
+    /// The levels of the same section as `level`, which is either a pack or the shipped levels.
+    pub fn section(&self, level: usize) -> Range<usize> {
+        self.pack_of(level)
+            .map_or(0..self.shipped(), |pack| pack.levels.clone())
+    }
+
+    /// The prerequisites of `level` which haven't been beaten yet.
+    ///
+    /// Prerequisites which aren't in the list are ignored, so that a
+    /// broken level doesn't lock all levels after it.
+    pub fn missing_requirements(&self, level: usize) -> Vec<&str> {
+        self.levels[level]
+            .meta
+            .requires
+            .iter()
+            .filter(|name| {
+                self.levels
+                    .iter()
+                    .position(|l| &l.name == *name)
+                    .map_or(false, |i| !self.beaten[i])
+            })
+            .map(|name| name.as_str())
+            .collect()
+    }
+
+    pub fn is_unlocked(&self, level: usize) -> bool {
+        self.missing_requirements(level).is_empty()
+    }
+
+    /// The level to play after beating `level`: the first unlocked level
+    /// after it in its section which hasn't been beaten yet, or else
+    /// the unlocked level directly after it.
+    pub fn next_level(&self, level: usize) -> Option<usize> {
+        let section = self.section(level);
+        let mut after = (level + 1..section.end).filter(|&i| self.is_unlocked(i));
+        let next = after.clone().next();
+        after.find(|&i| !self.beaten[i]).or(next)
+    }
+
+    /// Adds `level` after the shipped levels, returning its index.
+    pub fn add_level(&mut self, level: Level) -> usize {
+        let index = self.shipped();
+        self.levels.insert(index, level);
+        self.beaten.insert(index, false);
+        for pack in &mut self.packs {
+            pack.levels = pack.levels.start + 1..pack.levels.end + 1;
+        }
+        index
+    }
+}
+
+/// An error in a level, pointing to the place in the file which caused it.
+#[derive(Debug, Clone)]
+pub struct LevelError {
+    pub file: String,
+    /// The line and column of the error, both starting at 1.
+    pub location: Option<(usize, usize)>,
+    pub msg: String,
+}
+
+impl LevelError {
+    pub fn new(file: impl Into<String>, msg: impl Into<String>) -> Self {
+        LevelError {
+            file: file.into(),
+            location: None,
+            msg: msg.into(),
+        }
+    }
+
+    pub fn from_json(file: impl Into<String>, err: serde_json::Error) -> Self {
+        // the location is already part of `LevelError`.
+        let location = format!(" at line {} column {}", err.line(), err.column());
+        let msg = err.to_string();
+        LevelError {
+            file: file.into(),
+            location: Some((err.line(), err.column())),
+            msg: msg.trim_end_matches(&location).to_string(),
+        }
+    }
+}
+
+impl Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location {
+            Some((line, column)) => write!(f, "{}:{}:{}: {}", self.file, line, column, self.msg),
+            None => write!(f, "{}: {}", self.file, self.msg),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+/// An error in a level before knowing the file it is in.
+#[derive(Debug, Clone)]
+pub struct MapError {
+    /// The tile causing the error, if it is inside of the map.
+    pub pos: Option<GridPos>,
+    pub msg: String,
+}
+
+impl MapError {
+    /// Adds the file and the location of the tile, as computed by `locate`.
+    fn in_file(self, file: &str, locate: impl Fn(GridPos) -> Option<(usize, usize)>) -> LevelError {
+        LevelError {
+            file: file.to_string(),
+            location: self.pos.and_then(locate),
+            msg: self.msg,
+        }
+    }
+}
+
+/// The line and column of the byte at `offset`.
+fn line_column(src: &str, offset: usize) -> (usize, usize) {
+    let before = &src[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap().chars().count() + 1;
+    (line, column)
+}
+
+/// Finds the rows of the map in the source of a `.level` file, returning
+/// the offset of the first character of each row, after its opening quote.
+fn row_offsets(src: &str, rows: &[String]) -> Vec<Option<usize>> {
+    let mut offset = src.find("\"map\"").unwrap_or(0);
+    rows.iter()
+        .map(|row| {
+            let quoted = serde_json::to_string(row).unwrap();
+            let found = offset + src[offset..].find(&quoted)?;
+            offset = found + quoted.len();
+            Some(found + 1)
+        })
+        .collect()
+}
+
+/// What happens when a bot walks into another bot.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BotCollision {
+    /// The other bot is pushed like a box.
+    Push,
+    /// The other bot can't be moved, so the walking bot stays in place.
+    Block,
+}
+
+impl Default for BotCollision {
+    fn default() -> Self {
+        BotCollision::Push
+    }
+}
+
+/// A level in the old format, which only contains the entities
+/// and refers to a separate `.map` file for the tiles.
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct LevelSerde {
     pub map: String,
     pub boxes: Vec<(usize, usize)>,
     pub bots: Vec<(usize, usize, Direction)>,
+    #[serde(default)]
+    pub bot_collision: BotCollision,
 }
 
-#[derive(Clone, Debug)]
+/// The version of the `.level` format written by this build.
+pub const LEVEL_VERSION: u32 = 2;
+
+/// The number of memory cells of each bot.
+pub const MEMORY_CELLS: usize = 32;
+
+/// Everything about a level which doesn't affect the simulation.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct LevelMeta {
+    pub title: String,
+    pub author: String,
+    /// Explains the goal of the level before programming it.
+    pub briefing: String,
+    /// Hints which are revealed one at a time.
+    pub hints: Vec<String>,
+    /// How many memory cells the programs may use.
+    pub memory: usize,
+    /// The number of ticks of a good solution.
+    pub par: Option<u64>,
+    /// The names of the levels which have to be beaten before this one can be played.
+    pub requires: Vec<String>,
+}
+
+impl Default for LevelMeta {
+    fn default() -> Self {
+        LevelMeta {
+            title: String::new(),
+            author: String::new(),
+            briefing: String::new(),
+            hints: Vec::new(),
+            memory: MEMORY_CELLS,
+            par: None,
+            requires: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct BoxSerde {
+    pub x: usize,
+    pub y: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct BotSerde {
+    pub x: usize,
+    pub y: usize,
+    pub dir: Direction,
+}
+
+/// A `.level` file, containing the tiles, the entities and the metadata of a level.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LevelFile {
+    pub version: u32,
+    #[serde(flatten)]
+    pub meta: LevelMeta,
+    #[serde(default)]
+    pub bot_collision: BotCollision,
+    /// The rows of the map, using the same characters as `.map` files.
+    pub map: Vec<String>,
+    #[serde(default)]
+    pub boxes: Vec<BoxSerde>,
+    pub bots: Vec<BotSerde>,
+}
+
+/// An entry of `levels.json`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum LevelEntry {
+    /// The name of a `.level` file.
+    File(String),
+    Old(LevelSerde),
+}
+
+impl LevelEntry {
+    pub fn name(&self) -> &str {
+        match self {
+            LevelEntry::File(name) => name,
+            LevelEntry::Old(level) => &level.map,
+        }
+    }
+
+    /// The file in the `assets` directory which contains the rest of the level.
+    pub fn path(&self) -> String {
+        match self {
+            LevelEntry::File(name) => format!("levels/{}.level", name),
+            LevelEntry::Old(level) => format!("levels/{}.map", level.map),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Level {
+    pub name: String,
     pub map: Map,
     pub boxes: Vec<GridPos>,
     pub bots: Vec<(GridPos, Direction)>,
+    pub bot_collision: BotCollision,
+    pub meta: LevelMeta,
 }
 
 impl Level {
-    pub async fn from_level_serde(level_serde: LevelSerde) -> Self {
-        let map;
-        #[cfg(target_arch = "wasm32")]
-        {
-            use bevy_asset::AssetIo;
-            let assetio = bevy_asset::WasmAssetIo::new("./assets/");
-            let bytes = assetio
-                .load_path(std::path::Path::new(&format!(
-                    "./levels/{}.map",
-                    level_serde.map
-                )))
-                .await
-                .unwrap();
-            let string = String::from_utf8(bytes).unwrap();
-            map = Map::from_str(&string);
-        }
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            let map_str =
-                std::fs::read_to_string(format!("./assets/levels/{}.map", level_serde.map))
-                    .unwrap();
-            map = Map::from_str(&map_str);
+    /// Creates the level described by `entry`, with `src` being the contents of `entry.path()`.
+    ///
+    /// Levels in the old format are migrated to the current one.
+    pub fn from_entry(entry: LevelEntry, src: &str) -> Result<Self, LevelError> {
+        let path = entry.path();
+        match entry {
+            LevelEntry::File(name) => {
+                let file: LevelFile =
+                    serde_json::from_str(src).map_err(|e| LevelError::from_json(&path, e))?;
+                let rows = row_offsets(src, &file.map);
+                let locate = |GridPos(x, y): GridPos| {
+                    let row = file.map.get(y)?;
+                    let start = rows[y]?;
+                    let (byte, _) = row.char_indices().nth(x)?;
+                    // characters like `"` are escaped in the file.
+                    let escaped = serde_json::to_string(&row[..byte]).unwrap().len() - 2;
+                    Some(line_column(src, start + escaped))
+                };
+                Level::from_file(name, &file).map_err(|e| e.in_file(&path, locate))
+            }
+            LevelEntry::Old(level_serde) => {
+                let locate = |GridPos(x, y): GridPos| Some((y + 1, x + 1));
+                let map = Map::from_str(src).map_err(|e| e.in_file(&path, locate))?;
+                let level = Level::new(level_serde, map);
+                level.validate().map_err(|e| e.in_file(&path, locate))?;
+                Ok(level)
+            }
         }
+    }
 
+    /// A small level without any obstacles.
+    pub fn empty(name: String) -> Self {
+        let mut map = Map::filled(5, 3, Place::LowerFloor);
+        map.set_tile(GridPos(4, 1), Place::Exit);
         Level {
+            name,
+            map,
+            boxes: Vec::new(),
+            bots: vec![(GridPos(0, 1), Direction::Right)],
+            bot_collision: Default::default(),
+            meta: Default::default(),
+        }
+    }
+
+    /// Checks that there are tiles and bots and that all entities are on
+    /// different tiles, none of which is a wall, void or the exit.
+    pub fn validate(&self) -> Result<(), MapError> {
+        if self.map.width == 0 || self.map.height == 0 {
+            return Err(MapError {
+                pos: None,
+                msg: String::from("the map is empty"),
+            });
+        }
+        if self.bots.is_empty() {
+            return Err(MapError {
+                pos: None,
+                msg: String::from("the level has no bots"),
+            });
+        }
+
+        let entities = self
+            .bots
+            .iter()
+            .map(|&(pos, _)| ("bot", pos))
+            .chain(self.boxes.iter().map(|&pos| ("box", pos)));
+        let mut seen = Vec::new();
+        for (kind, pos @ GridPos(x, y)) in entities {
+            if x >= self.map.width || y >= self.map.height {
+                return Err(MapError {
+                    pos: None,
+                    msg: format!(
+                        "the {} at ({}, {}) is outside of the {}x{} map",
+                        kind, x, y, self.map.width, self.map.height
+                    ),
+                });
+            }
+
+            let tile = match self.map.tile(pos) {
+                Place::Wall => Some("a wall"),
+                Place::Void => Some("void"),
+                Place::Exit => Some("the exit"),
+                _ => None,
+            };
+            let msg = if let Some(tile) = tile {
+                format!("the {} at ({}, {}) is on {}", kind, x, y, tile)
+            } else if let Some((other, _)) = seen.iter().find(|&&(_, p)| p == pos) {
+                format!(
+                    "the {} at ({}, {}) is on the same tile as a {}",
+                    kind, x, y, other
+                )
+            } else {
+                seen.push((kind, pos));
+                continue;
+            };
+            return Err(MapError {
+                pos: Some(pos),
+                msg,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn new(level_serde: LevelSerde, map: Map) -> Self {
+        Level {
+            meta: LevelMeta {
+                title: level_serde.map.clone(),
+                ..Default::default()
+            },
+            name: level_serde.map,
             map,
             boxes: level_serde
                 .boxes
@@ -70,11 +466,71 @@ impl Level {
                 .into_iter()
                 .map(|(x, y, dir)| (GridPos(x, y), dir))
                 .collect(),
+            bot_collision: level_serde.bot_collision,
+        }
+    }
+
+    pub fn from_file(name: String, file: &LevelFile) -> Result<Self, MapError> {
+        let err = |msg| MapError { pos: None, msg };
+        if file.version != LEVEL_VERSION {
+            return Err(err(format!(
+                "the level uses version {} of the format, expected {}",
+                file.version, LEVEL_VERSION
+            )));
+        }
+        if file.meta.memory > MEMORY_CELLS {
+            return Err(err(format!(
+                "the level has {} memory cells, but bots only have {}",
+                file.meta.memory, MEMORY_CELLS
+            )));
+        }
+
+        if let Some(y) = file.map.iter().position(|row| row.contains('\n')) {
+            return Err(err(format!("row {} of the map contains a line break", y)));
+        }
+
+        // unlike `.map` files, trailing void tiles are kept.
+        let mut map = Map::from_str(&file.map.join("\n"))?;
+        let width = file.map.iter().map(|row| row.chars().count()).max();
+        map.resize(width.unwrap_or(0).max(map.width), map.height);
+        let level = Level {
+            name,
+            map,
+            boxes: file.boxes.iter().map(|b| GridPos(b.x, b.y)).collect(),
+            bots: file
+                .bots
+                .iter()
+                .map(|b| (GridPos(b.x, b.y), b.dir))
+                .collect(),
+            bot_collision: file.bot_collision,
+            meta: file.meta.clone(),
+        };
+        level.validate()?;
+        Ok(level)
+    }
+
+    /// The inverse of `Level::from_file`.
+    pub fn to_file(&self) -> LevelFile {
+        LevelFile {
+            version: LEVEL_VERSION,
+            meta: self.meta.clone(),
+            bot_collision: self.bot_collision,
+            map: self.map.render().lines().map(String::from).collect(),
+            boxes: self
+                .boxes
+                .iter()
+                .map(|&GridPos(x, y)| BoxSerde { x, y })
+                .collect(),
+            bots: self
+                .bots
+                .iter()
+                .map(|&(GridPos(x, y), dir)| BotSerde { x, y, dir })
+                .collect(),
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct Map {
     pub width: usize,
     pub height: usize,
@@ -93,12 +549,12 @@ impl std::fmt::Debug for Map {
 }
 
 impl Map {
-    pub fn from_str(src: &str) -> Self {
+    pub fn from_str(src: &str) -> Result<Self, MapError> {
         let mut lines = Vec::new();
-        for l in src.lines() {
+        for (y, l) in src.lines().enumerate() {
             let l = l.trim_end();
             let mut line = Vec::new();
-            for c in l.chars() {
+            for (x, c) in l.chars().enumerate() {
                 line.push(match c {
                     '-' => Place::UpperFloor,
                     '.' => Place::LowerFloor,
@@ -109,13 +565,22 @@ impl Map {
                     ' ' => Place::Void,
                     '#' => Place::Wall,
                     'o' => Place::Exit,
-                    _ => panic!("unexpected {:?}", c),
+                    _ => {
+                        return Err(MapError {
+                            pos: Some(GridPos(x, y)),
+                            msg: format!(
+                                "unexpected character {:?}, expected one of \
+                                `-`, `.`, `^`, `v`, `<`, `>`, `#`, `o`, or a space",
+                                c
+                            ),
+                        });
+                    }
                 });
             }
             lines.push(line);
         }
 
-        let width = lines.iter().map(|l| l.len()).max().unwrap();
+        let width = lines.iter().map(|l| l.len()).max().unwrap_or(0);
         let mut layout = Vec::with_capacity(width * lines.len());
         for line in lines.iter() {
             let len = line.len();
@@ -125,10 +590,66 @@ impl Map {
             }
         }
 
-        Map {
+        Ok(Map {
             width,
             height: lines.len(),
             layout,
+        })
+    }
+
+    /// A `width` by `height` map only containing `place`.
+    pub fn filled(width: usize, height: usize, place: Place) -> Self {
+        Map {
+            width,
+            height,
+            layout: vec![place; width * height],
+        }
+    }
+
+    /// The inverse of `Map::from_str`.
+    ///
+    /// Trailing `Place::Void` columns are lost when parsing the result
+    /// with `Map::from_str` again, but not in `.level` files.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for chunk in self.layout.chunks(self.width) {
+            for &place in chunk {
+                out.push(match place {
+                    Place::UpperFloor => '-',
+                    Place::LowerFloor => '.',
+                    Place::Ramp(Direction::Up) => '^',
+                    Place::Ramp(Direction::Down) => 'v',
+                    Place::Ramp(Direction::Left) => '<',
+                    Place::Ramp(Direction::Right) => '>',
+                    Place::Void => ' ',
+                    Place::Wall => '#',
+                    Place::Exit => 'o',
+                });
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Changes the size of the map, keeping the top left corner and
+    /// filling new tiles with `Place::Void`.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        let mut layout = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                layout.push(self.tile(GridPos(x, y)));
+            }
+        }
+        *self = Map {
+            width,
+            height,
+            layout,
+        };
+    }
+
+    pub fn set_tile(&mut self, GridPos(x, y): GridPos, place: Place) {
+        if x < self.width && y < self.height {
+            self.layout[y * self.width + x] = place;
         }
     }
 
@@ -153,5 +674,20 @@ pub struct BoxData {
     pub start_position: GridPos,
 }
 
-#[derive(Component, Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Component, Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
 pub struct GridPos(pub usize, pub usize);
+
+impl GridPos {
+    /// The adjacent position in direction `dir`.
+    ///
+    /// Walking off the top or left edge of the map results in a position
+    /// which is out of bounds and therefore `Place::Void`.
+    pub fn neighbour(self, dir: Direction) -> GridPos {
+        match dir {
+            Direction::Up => GridPos(self.0, self.1.wrapping_sub(1)),
+            Direction::Down => GridPos(self.0, self.1 + 1),
+            Direction::Left => GridPos(self.0.wrapping_sub(1), self.1),
+            Direction::Right => GridPos(self.0 + 1, self.1),
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::bot::sim::{SimEntity, Simulation};
+use crate::bot::SimEntities;
+use crate::draw::{self, DrawUpdates};
+use crate::map::{GridPos, Level};
+
+/// The version of the replay format written by this build.
+pub const VERSION: u32 = 1;
+
+/// A recorded run of a level, which can be watched without running the programs.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Replay {
+    pub version: u32,
+    /// The name of the map of the level.
+    pub level: String,
+    pub programs: Vec<[u8; 32]>,
+    /// The render steps of each tick, one batch per bot.
+    pub ticks: Vec<Vec<Vec<(SimEntity, draw::Step)>>>,
+    pub checksum: u64,
+}
+
+/// The 64 bit FNV-1a hash, which unlike `DefaultHasher`
+/// is guaranteed to stay the same across builds.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325;
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Which of the two replays of a level is meant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayKind {
+    /// Saved with the `Save` button while running.
+    Saved,
+    /// Recorded automatically after beating the level.
+    Won,
+}
+
+impl Replay {
+    /// A replay of a run of `programs` on `level`, which showed the render steps `ticks`.
+    pub fn new(
+        level: &Level,
+        programs: &[[u8; 32]],
+        ticks: Vec<Vec<Vec<(SimEntity, draw::Step)>>>,
+    ) -> Self {
+        let mut replay = Replay {
+            version: VERSION,
+            level: level.name.clone(),
+            programs: programs.to_vec(),
+            ticks,
+            checksum: 0,
+        };
+        replay.checksum = replay.compute_checksum();
+        replay
+    }
+
+    fn compute_checksum(&self) -> u64 {
+        let data = (self.version, &self.level, &self.programs, &self.ticks);
+        fnv1a(&serde_json::to_vec(&data).unwrap())
+    }
+
+    /// The most recently written replay of `level`.
+    pub fn latest(level: &Level) -> Result<PathBuf, String> {
+        let modified = |path: &PathBuf| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        let mut paths = Vec::new();
+        for kind in [ReplayKind::Saved, ReplayKind::Won] {
+            paths.push(Replay::path(level, kind)?);
+        }
+        paths
+            .into_iter()
+            .filter_map(|path| Some((modified(&path)?, path)))
+            .max()
+            .map(|(_, path)| path)
+            .ok_or_else(|| format!("there is no replay of `{}` yet", level.name))
+    }
+
+    /// Checks that running the programs of the replay on `level` still
+    /// results in the recorded steps, which is not the case if either
+    /// the level or the rules of the game changed since recording it.
+    pub fn verify(&self, level: &Level) -> Result<(), String> {
+        if self.level != level.name {
+            return Err(format!(
+                "the replay is for the level `{}`, not `{}`",
+                self.level, level.name
+            ));
+        }
+        if self.programs.len() != level.bots.len() {
+            return Err(format!(
+                "the replay has {} programs but the level has {} bots",
+                self.programs.len(),
+                level.bots.len()
+            ));
+        }
+
+        let mut sim = Simulation::from_level(level, &self.programs);
+        match self.ticks.iter().position(|tick| *tick != sim.step()) {
+            Some(tick) => Err(format!(
+                "the replay no longer matches the current rules of the level, \
+                it diverges in tick {}",
+                tick + 1
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Replay {
+    /// Replays are stored next to the save game, separately for each kind
+    /// so that winning doesn't overwrite a replay saved by hand.
+    pub fn path(level: &Level, kind: ReplayKind) -> Result<PathBuf, String> {
+        let file = match kind {
+            ReplayKind::Saved => format!("{}.replay", level.name),
+            ReplayKind::Won => format!("{}.won.replay", level.name),
+        };
+        dirs::data_dir()
+            .map(|dir| dir.join("shitty-bot-game").join("replays").join(file))
+            .ok_or_else(|| String::from("there is no user data directory to store replays in"))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("failed to create {:?}: {}", dir, e))?;
+        }
+        std::fs::write(path, serde_json::to_vec(self).unwrap())
+            .map_err(|e| format!("failed to write {:?}: {}", path, e))
+    }
+
+    /// Loads a replay, checking that it wasn't modified since recording it.
+    pub fn load(path: &Path) -> Result<Replay, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("failed to read {:?}: {}", path, e))?;
+        let replay: Replay = serde_json::from_slice(&bytes)
+            .map_err(|e| format!("invalid replay {:?}: {}", path, e))?;
+        if replay.version != VERSION {
+            return Err(format!(
+                "the replay {:?} uses version {} of the format, expected {}",
+                path, replay.version, VERSION
+            ));
+        }
+        if replay.checksum != replay.compute_checksum() {
+            return Err(format!("the replay {:?} is corrupted", path));
+        }
+        Ok(replay)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Replay {
+    pub fn path(_: &Level, _: ReplayKind) -> Result<PathBuf, String> {
+        Err(String::from("replays are not supported in the browser"))
+    }
+
+    pub fn save(&self, _: &Path) -> Result<(), String> {
+        Err(String::from(
+            "saving replays is not supported in the browser",
+        ))
+    }
+
+    pub fn load(_: &Path) -> Result<Replay, String> {
+        Err(String::from(
+            "loading replays is not supported in the browser",
+        ))
+    }
+}
+
+/// The replay currently being watched.
+pub struct ReplayPlayback {
+    pub replay: Replay,
+    /// The number of ticks which have already been played.
+    pub tick: usize,
+}
+
+pub fn init_playback(world: &mut World) {
+    let entities = SimEntities::from_world(world);
+    world.insert_resource(entities);
+}
+
+/// Feeds the next tick of the replay to `update_map_system` once
+/// the previous one has been drawn.
+pub fn progress_playback(
+    mut render_steps: ResMut<DrawUpdates>,
+    mut playback: ResMut<ReplayPlayback>,
+    entities: Res<SimEntities>,
+    mut positions: Query<&mut GridPos>,
+) {
+    if !render_steps.data.is_empty() {
+        return;
+    }
+
+    let tick = playback.tick;
+    let batches = if let Some(batches) = playback.replay.ticks.get(tick) {
+        batches
+    } else {
+        return;
+    };
+
+    for batch in batches {
+        let changes = batch
+            .iter()
+            .map(|&(e, step)| (entities.get(e), step))
+            .collect();
+        render_steps.data.push_back(changes);
+
+        for &(e, step) in batch {
+            if let draw::Step::Move(_, to) = step {
+                *positions.get_mut(entities.get(e)).unwrap() = to;
+            }
+        }
+    }
+    playback.tick += 1;
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::{Path, PathBuf};
+
+#[cfg(not(target_arch = "wasm32"))]
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::bot::program;
+
+/// The version of the save format written by this build.
+///
+/// All fields added after the first version have defaults,
+/// so older saves can always be loaded.
+pub const VERSION: u32 = 1;
+
+/// The progress of the player, stored in the user data directory.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SaveGame {
+    pub version: u32,
+    /// The progress for each level, keyed by the name of its map.
+    #[serde(default)]
+    pub levels: HashMap<String, LevelProgress>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct LevelProgress {
+    pub beaten: bool,
+    /// The fewest ticks needed by a winning program.
+    pub best_ticks: Option<u64>,
+    /// The fewest cells used by a winning program, summed over all bots.
+    pub best_cells: Option<usize>,
+    /// The program of each bot as it was last edited.
+    pub programs: Vec<[u8; 32]>,
+}
+
+impl Default for SaveGame {
+    fn default() -> Self {
+        SaveGame {
+            version: VERSION,
+            levels: HashMap::new(),
+        }
+    }
+}
+
+impl SaveGame {
+    pub fn level(&mut self, name: &str) -> &mut LevelProgress {
+        self.levels.entry(name.to_string()).or_default()
+    }
+
+    pub fn is_beaten(&self, name: &str) -> bool {
+        self.levels.get(name).map_or(false, |level| level.beaten)
+    }
+
+    pub fn record_win(&mut self, name: &str, ticks: u64, programs: &[[u8; 32]]) {
+        let cells = programs.iter().map(program::used_cells).sum();
+        let level = self.level(name);
+        level.beaten = true;
+        level.best_ticks = Some(level.best_ticks.map_or(ticks, |t| t.min(ticks)));
+        level.best_cells = Some(level.best_cells.map_or(cells, |c| c.min(cells)));
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl SaveGame {
+    pub fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("shitty-bot-game").join("save.json"))
+    }
+
+    fn load_from(path: &Path) -> anyhow::Result<SaveGame> {
+        let bytes = std::fs::read(path).with_context(|| format!("failed to read {:?}", path))?;
+        let save: SaveGame = serde_json::from_slice(&bytes)
+            .with_context(|| format!("failed to parse {:?}", path))?;
+        if save.version > VERSION {
+            eprintln!(
+                "{:?} was written by a newer version of the game, some progress may be lost",
+                path
+            );
+        }
+        Ok(save)
+    }
+
+    /// Loads the save game, starting from scratch if there is none.
+    ///
+    /// A save which can't be read is moved aside instead of being overwritten.
+    pub fn load() -> SaveGame {
+        let path = match SaveGame::path() {
+            Some(path) if path.exists() => path,
+            _ => return SaveGame::default(),
+        };
+        match SaveGame::load_from(&path) {
+            Ok(save) => save,
+            Err(e) => {
+                let backup = path.with_extension("json.bak");
+                eprintln!("{:#}, moving it to {:?}", e, backup);
+                if let Err(e) = std::fs::rename(&path, &backup) {
+                    eprintln!("failed to move {:?}: {}", path, e);
+                }
+                SaveGame::default()
+            }
+        }
+    }
+
+    pub fn store(&mut self) {
+        let path = if let Some(path) = SaveGame::path() {
+            path
+        } else {
+            return;
+        };
+        self.version = VERSION;
+        let result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| std::fs::write(&path, serde_json::to_vec_pretty(self).unwrap()));
+        if let Err(e) = result {
+            eprintln!("failed to write {:?}: {}", path, e);
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl SaveGame {
+    pub fn load() -> SaveGame {
+        SaveGame::default()
+    }
+
+    pub fn store(&mut self) {}
+}
@@ -0,0 +1,146 @@
+use crate::bot::sim::{Outcome, Simulation};
+use crate::bot::Instruction;
+use crate::map::Level;
+
+pub struct Solution {
+    pub instructions: [u8; 32],
+    pub cells: usize,
+    pub ticks: u64,
+}
+
+enum Frontier {
+    /// A bot is about to execute a cell which has not been decided yet.
+    Reached(Simulation),
+    Won(u64),
+    Dead,
+}
+
+/// Searches for the shortest program solving a level, with all bots
+/// running the same program.
+///
+/// Programs are built one instruction at a time. As the cells after the
+/// current prefix have not been decided yet, the simulation only has to
+/// run until a bot first reaches one of these cells. Any prefix which fails,
+/// or does not reach the end of the prefix within `max_ticks`, is discarded
+/// together with all of its extensions.
+///
+/// Programs writing to memory are not searched, as an undecided cell
+/// could otherwise be changed before it is reached.
+pub struct Solver<'a> {
+    level: &'a Level,
+    max_ticks: u64,
+    /// The number of prefixes simulated so far.
+    pub explored: u64,
+}
+
+impl<'a> Solver<'a> {
+    pub fn new(level: &'a Level, max_ticks: u64) -> Self {
+        Solver {
+            level,
+            max_ticks,
+            explored: 0,
+        }
+    }
+
+    /// Finds a program using the fewest cells, trying all lengths up to `max_cells`.
+    pub fn solve(&mut self, max_cells: usize) -> Option<Solution> {
+        let programs = vec![[0; 32]; self.level.bots.len()];
+        let sim = Simulation::from_level(self.level, &programs);
+        (1..=max_cells.min(32)).find_map(|len| match self.run_to_frontier(sim.clone(), 0) {
+            Frontier::Reached(sim) => self.search(sim, 0, len),
+            Frontier::Won(_) | Frontier::Dead => None,
+        })
+    }
+
+    fn candidates(&self, len: usize) -> Vec<(Instruction, Option<u8>)> {
+        let mut candidates = vec![
+            (Instruction::TurnLeft, None),
+            (Instruction::TurnRight, None),
+            (Instruction::TurnAround, None),
+        ];
+        // waiting for or looking at other bots only matters if there are any.
+        let mut branches = vec![
+            Instruction::Goto,
+            Instruction::IfBox,
+            Instruction::IfWall,
+            Instruction::IfEdge,
+            Instruction::IfNotBox,
+            Instruction::IfNotWall,
+            Instruction::IfNotEdge,
+        ];
+        if self.level.bots.len() > 1 {
+            candidates.push((Instruction::Skip, None));
+            branches.extend([Instruction::IfBot, Instruction::IfNotBot]);
+        }
+
+        let max_walk = self.level.map.width.max(self.level.map.height);
+        candidates.extend((1..=max_walk.min(31)).map(|n| (Instruction::Walk, Some(n as u8))));
+        for instr in branches {
+            candidates.extend((0..len as u8).map(|target| (instr, Some(target))));
+        }
+        candidates
+    }
+
+    fn run_to_frontier(&mut self, mut sim: Simulation, decided: usize) -> Frontier {
+        self.explored += 1;
+        loop {
+            match sim.outcome() {
+                Some(Outcome::Won) => return Frontier::Won(sim.tick),
+                Some(Outcome::Failed(_)) => return Frontier::Dead,
+                None => {}
+            }
+
+            let at_frontier = sim.bots.iter().any(|bot| {
+                bot.state
+                    .next_instruction()
+                    .map_or(false, |cell| cell as usize >= decided)
+            });
+            if at_frontier {
+                return Frontier::Reached(sim);
+            }
+
+            if sim.tick >= self.max_ticks {
+                return Frontier::Dead;
+            }
+            sim.step();
+        }
+    }
+
+    fn search(&mut self, sim: Simulation, decided: usize, len: usize) -> Option<Solution> {
+        for (instr, operand) in self.candidates(len) {
+            let cells = decided + 1 + operand.is_some() as usize;
+            if cells > len {
+                continue;
+            }
+
+            let mut sim = sim.clone();
+            for bot in sim.bots.iter_mut() {
+                for memory in [&mut bot.instructions, &mut bot.state.memory] {
+                    memory[decided] = instr.repr();
+                    if let Some(operand) = operand {
+                        memory[decided + 1] = operand;
+                    }
+                }
+            }
+            let instructions = sim.bots[0].instructions;
+
+            match self.run_to_frontier(sim, cells) {
+                Frontier::Won(ticks) => {
+                    return Some(Solution {
+                        instructions,
+                        cells,
+                        ticks,
+                    })
+                }
+                Frontier::Reached(sim) if cells < len => {
+                    if let Some(solution) = self.search(sim, cells, len) {
+                        return Some(solution);
+                    }
+                }
+                Frontier::Reached(_) | Frontier::Dead => {}
+            }
+        }
+
+        None
+    }
+}
@@ -7,10 +7,28 @@ use bevy::prelude::*;
 #[derive(Component)]
 pub struct LevelId(usize);
 
+/// A level which could not be loaded, an index into `LevelList::broken`.
+#[derive(Component)]
+pub struct BrokenLevel(usize);
+
+/// Shows why the hovered level is broken or locked.
+#[derive(Component)]
+pub struct LevelInfoText;
+
+/// The title of a level pack.
+#[derive(Component)]
+pub struct PackTitle;
+
+/// Opens the level editor with the last played level.
+#[derive(Component)]
+pub struct EditLevels;
+
 const NOT_DONE: Color = Color::rgb(0.2, 0.5, 0.2);
 const DONE: Color = Color::rgb(0.4, 1.0, 0.4);
+const BROKEN: Color = Color::rgb(0.3, 0.3, 0.3);
+const LOCKED: Color = Color::rgb(0.2, 0.25, 0.2);
 
-pub fn init(mut commands: Commands, levels: Res<LevelList>, asset_server: Res<AssetServer>) {
+pub fn init(mut commands: Commands, mut levels: ResMut<LevelList>, asset_server: Res<AssetServer>) {
     commands
         .spawn_bundle(TextBundle {
             style: Style {
@@ -53,9 +71,138 @@ pub fn init(mut commands: Commands, levels: Res<LevelList>, asset_server: Res<As
         })
         .insert(StateLocal);
 
-    for (i, &beaten) in levels.beaten.iter().enumerate() {
-        commands
-            .spawn_bundle(ButtonBundle {
+    // the level buttons are spawned by `refresh`.
+    levels.set_changed();
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Percent(5.0),
+                    right: Val::Percent(5.0),
+                    top: Val::Auto,
+                    bottom: Val::Percent(15.0),
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 20.0,
+                    color: Color::rgb(0.9, 0.5, 0.5),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(LevelInfoText)
+        .insert(StateLocal);
+
+    commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Auto, Val::Auto),
+                position_type: PositionType::Absolute,
+                margin: Rect::all(Val::Auto),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                position: Rect {
+                    left: Val::Percent(40.0),
+                    right: Val::Percent(40.0),
+                    top: Val::Auto,
+                    bottom: Val::Percent(5.0),
+                },
+                ..Default::default()
+            },
+            color: NOT_DONE.into(),
+            ..Default::default()
+        })
+        .insert(EditLevels)
+        .insert(StateLocal)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    "level editor",
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 25.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+        });
+}
+
+/// Spawns one button for each level, followed by the broken levels.
+///
+/// Each level pack gets its own section below the shipped levels.
+fn spawn_level_buttons(commands: &mut Commands, levels: &LevelList, asset_server: &AssetServer) {
+    let mut sections = vec![(None, 0..levels.shipped())];
+    sections.extend(
+        levels
+            .packs
+            .iter()
+            .map(|pack| (Some(pack), pack.levels.clone())),
+    );
+    // broken levels are shown after all others and can't be selected.
+    let total = levels.levels.len();
+    sections.push((None, total..total + levels.broken.len()));
+
+    let mut top = 30.0;
+    for (pack, range) in sections {
+        if let Some(pack) = pack {
+            let title = if pack.author.is_empty() {
+                pack.title.clone()
+            } else {
+                format!("{} by {}", pack.title, pack.author)
+            };
+            commands
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: Rect {
+                            left: Val::Percent(20.0),
+                            top: Val::Percent(top),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        title,
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 25.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(PackTitle)
+                .insert(StateLocal);
+            top += 5.0;
+        }
+
+        let (start, len) = (range.start, range.len());
+        for i in range {
+            let (column, row) = ((i - start) % 6, (i - start) / 6);
+            let (color, label) = if i < total {
+                let color = if levels.beaten[i] {
+                    DONE
+                } else if levels.is_unlocked(i) {
+                    NOT_DONE
+                } else {
+                    LOCKED
+                };
+                (color, (i - start).to_string())
+            } else {
+                (BROKEN, String::from("!"))
+            };
+            let mut button = commands.spawn_bundle(ButtonBundle {
                 style: Style {
                     size: Size::new(Val::Auto, Val::Auto),
                     position_type: PositionType::Absolute,
@@ -63,22 +210,25 @@ pub fn init(mut commands: Commands, levels: Res<LevelList>, asset_server: Res<As
                     justify_content: JustifyContent::Center,
                     align_items: AlignItems::Center,
                     position: Rect {
-                        left: Val::Percent(20.0 + (i % 6) as f32 * 10.0),
-                        right: Val::Percent(75.0 - (i % 6) as f32 * 10.0),
-                        top: Val::Percent(30.0 + (i / 6) as f32 * 10.0),
-                        bottom: Val::Percent(65.0 - (i / 6) as f32 * 10.0),
+                        left: Val::Percent(20.0 + column as f32 * 10.0),
+                        right: Val::Percent(75.0 - column as f32 * 10.0),
+                        top: Val::Percent(top + row as f32 * 10.0),
+                        bottom: Val::Percent(65.0 - top - row as f32 * 10.0),
                     },
                     ..Default::default()
                 },
-                color: if beaten { DONE.into() } else { NOT_DONE.into() },
+                color: color.into(),
                 ..Default::default()
-            })
-            .insert(LevelId(i))
-            .insert(StateLocal)
-            .with_children(|parent| {
+            });
+            if i < total {
+                button.insert(LevelId(i));
+            } else {
+                button.insert(BrokenLevel(i - total));
+            }
+            button.insert(StateLocal).with_children(|parent| {
                 parent.spawn_bundle(TextBundle {
                     text: Text::with_section(
-                        i.to_string(),
+                        label,
                         TextStyle {
                             font: asset_server.load("fonts/FiraSans-Bold.ttf"),
                             font_size: 25.0,
@@ -89,21 +239,62 @@ pub fn init(mut commands: Commands, levels: Res<LevelList>, asset_server: Res<As
                     ..Default::default()
                 });
             });
+        }
+        top += len.div_ceil(6) as f32 * 10.0;
+    }
+}
+
+/// Spawns the level buttons whenever the levels changed,
+/// for example after reloading them.
+pub fn refresh(
+    mut commands: Commands,
+    levels: Res<LevelList>,
+    asset_server: Res<AssetServer>,
+    buttons: Query<Entity, Or<(With<LevelId>, With<BrokenLevel>, With<PackTitle>)>>,
+) {
+    if !levels.is_changed() {
+        return;
     }
+
+    for entity in buttons.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_level_buttons(&mut commands, &levels, &asset_server);
 }
 
 pub fn update(
     mut state: ResMut<State<GameState>>,
     mut current_level: ResMut<CurrentLevel>,
     interaction_query: Query<(&LevelId, &Interaction), (Changed<Interaction>, With<Button>)>,
+    edit_query: Query<&Interaction, (Changed<Interaction>, With<EditLevels>)>,
+    broken_query: Query<(&BrokenLevel, &Interaction), Changed<Interaction>>,
+    levels: Res<LevelList>,
+    mut info_text: Query<&mut Text, With<LevelInfoText>>,
 ) {
+    for (broken, interaction) in broken_query.iter() {
+        info_text.single_mut().sections[0].value = match interaction {
+            Interaction::Clicked | Interaction::Hovered => levels.broken[broken.0].to_string(),
+            Interaction::None => String::new(),
+        };
+    }
+
+    if let Some(Interaction::Clicked) = edit_query.iter().next() {
+        state.set(GameState::LevelEditor).unwrap();
+    }
+
     for (level_id, interaction) in interaction_query.iter() {
+        let missing = levels.missing_requirements(level_id.0);
         match interaction {
-            Interaction::Clicked => {
+            Interaction::Clicked if missing.is_empty() => {
                 current_level.0 = level_id.0;
                 state.set(GameState::ChangeLevel).unwrap();
             }
-            Interaction::Hovered | Interaction::None => {}
+            Interaction::Clicked | Interaction::Hovered if !missing.is_empty() => {
+                info_text.single_mut().sections[0].value =
+                    format!("beat {} to unlock this level", missing.join(", "));
+            }
+            Interaction::Clicked | Interaction::Hovered => {}
+            Interaction::None => info_text.single_mut().sections[0].value = String::new(),
         }
     }
 }
@@ -0,0 +1,133 @@
+use super::buttons::*;
+use super::CornerButton;
+use crate::bot::Instruction;
+use crate::map::Place;
+use crate::util::StateLocal;
+use bevy::prelude::*;
+
+pub struct ManualButton(Entity);
+impl CornerButton for ManualButton {
+    const MK: fn(Entity) -> Self = ManualButton;
+    const MSG: &'static str = "Manual";
+    const TOP: f32 = 56.0;
+}
+
+/// The open manual, which is closed by clicking anywhere on it.
+#[derive(Component)]
+pub struct ManualOverlay;
+
+const OVERLAY: Color = Color::rgba(0.05, 0.05, 0.05, 0.95);
+
+const RULES: &str = "\
+Each tick, every bot which is done with its last instruction executes the next one. \
+The operands of an instruction are stored in the cells after it. Walking, turning, \
+skipping, jumps, branches and writing to memory all take a tick.
+
+Programs can change their own memory with `store`, `inc` and `dec`, for example to \
+count how often a loop ran. Writing outside of the memory halts the bot. Each run \
+starts again from the program as it was written.
+
+Walking into a box pushes it one tile ahead, which only works if the box could walk \
+there itself. A row of boxes is pushed all at once. Depending on the level, bots are \
+either pushed like boxes or block each other. Bots walking onto the same tile or into \
+each other don't move at all.
+
+Ramps point downhill. They are entered from the upper floor by walking downhill and \
+from the lower floor by walking uphill, and can only be left in the same directions.";
+
+/// All instructions with the syntax of their operand.
+fn instructions_text() -> String {
+    let mut text = String::from("Instructions\n\n");
+    for instr in (0..).map_while(Instruction::from_repr) {
+        let operands = instr
+            .operands()
+            .iter()
+            .map(|name| format!(" <{}>", name))
+            .collect::<String>();
+        text += &format!("{}{}: {}\n", instr, operands, instr.manual());
+    }
+    text
+}
+
+fn tiles_and_rules_text() -> String {
+    let mut text = String::from("Tiles\n\n");
+    for place in Place::MANUAL {
+        let (name, manual) = place.manual();
+        text += &format!("{}: {}\n", name, manual);
+    }
+    text + "\nRules\n\n" + RULES
+}
+
+fn spawn_overlay(commands: &mut Commands, asset_server: &AssetServer) {
+    let column = |text: String, left: f32, right: f32| TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: Rect {
+                left: Val::Percent(left),
+                right: Val::Percent(right),
+                top: Val::Percent(3.0),
+                bottom: Val::Auto,
+            },
+            max_size: Size::new(Val::Percent(100.0 - left - right), Val::Undefined),
+            ..Default::default()
+        },
+        text: Text::with_section(
+            text,
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 18.0,
+                color: Color::rgb(0.9, 0.9, 0.9),
+            },
+            Default::default(),
+        ),
+        ..Default::default()
+    };
+
+    commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            color: OVERLAY.into(),
+            ..Default::default()
+        })
+        .insert(ManualOverlay)
+        .insert(StateLocal)
+        .with_children(|parent| {
+            parent.spawn_bundle(column(instructions_text(), 3.0, 52.0));
+            parent.spawn_bundle(column(tiles_and_rules_text(), 52.0, 3.0));
+        });
+}
+
+/// Opens or closes the manual after clicking the `Manual` button or pressing `F1`.
+pub fn toggle(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    input: Res<Input<KeyCode>>,
+    button: Res<ManualButton>,
+    mut interaction_query: Query<
+        (&Interaction, &mut UiColor),
+        (Changed<Interaction>, With<Button>),
+    >,
+    overlay: Query<(Entity, &Interaction), With<ManualOverlay>>,
+) {
+    let mut clicked = input.just_pressed(KeyCode::F1);
+    if let Ok((interaction, mut color)) = interaction_query.get_mut(button.0) {
+        match *interaction {
+            Interaction::Clicked => clicked = true,
+            Interaction::Hovered => *color = HOVERED_BUTTON.into(),
+            Interaction::None => *color = NORMAL_BUTTON.into(),
+        }
+    }
+
+    match overlay.iter().next() {
+        Some((entity, interaction)) if clicked || *interaction == Interaction::Clicked => {
+            commands.entity(entity).despawn_recursive();
+        }
+        Some(_) => {}
+        None if clicked => spawn_overlay(&mut commands, &asset_server),
+        None => {}
+    }
+}
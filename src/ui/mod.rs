@@ -1,11 +1,16 @@
 use std::iter;
 
+use crate::bot::edit::ProgramEditors;
 use crate::bot::BotData;
 use crate::bot::Instruction;
+use crate::map::Level;
 use crate::util::StateLocal;
+use crate::GameState;
 use bevy::prelude::*;
 
+pub mod manual;
 pub mod programming;
+pub mod replay;
 pub mod running;
 
 mod buttons {
@@ -15,6 +20,8 @@ mod buttons {
     pub const VALID_MEM: Color = Color::rgb(0.1, 0.1, 0.1);
     pub const INVALID_MEM: Color = Color::rgb(0.8, 0.3, 0.3);
     pub const SELECTED_MEM: Color = Color::rgb(0.1, 0.5, 0.1);
+    pub const BREAKPOINT: Color = Color::rgb(0.5, 0.1, 0.1);
+    pub const CHANGED_MEM: Color = Color::rgb(0.2, 0.2, 0.5);
 }
 use buttons::*;
 
@@ -31,6 +38,21 @@ pub struct MemUiData;
 
 pub struct ErrorText(Entity);
 
+impl ErrorText {
+    /// Shows `msg` as an error below the memory.
+    pub fn show(
+        &self,
+        msg: String,
+        color: &mut Query<&mut UiColor>,
+        children: &Query<&Children>,
+        text: &mut Query<&mut Text>,
+    ) {
+        *color.get_mut(self.0).unwrap() = ERROR.into();
+        let text_entity = children.get(self.0).unwrap()[0];
+        text.get_mut(text_entity).unwrap().sections[0].value = msg;
+    }
+}
+
 pub fn init(mut commands: Commands, asset_server: Res<AssetServer>) {
     let error_text = commands
         .spawn_bundle(ButtonBundle {
@@ -189,6 +211,7 @@ pub fn init(mut commands: Commands, asset_server: Res<AssetServer>) {
 pub fn refresh_mem(
     mem_ui: Res<MemUi>,
     error: Res<ErrorText>,
+    editors: Res<ProgramEditors>,
     mem: Query<&BotData>,
     mut color: Query<&mut UiColor>,
     children: Query<&Children>,
@@ -201,7 +224,7 @@ pub fn refresh_mem(
         text.get_mut(text_entity).unwrap().sections[0].value = String::new();
     }
 
-    for mem in mem.iter() {
+    for mem in mem.iter().filter(|mem| mem.index == editors.active) {
         let iter = iter::zip(
             &mem.instructions,
             iter::zip(&mem_ui.user_names, &mem_ui.user_values),
@@ -232,9 +255,108 @@ pub fn clear_mem(mut commands: Commands, to_remove: Query<(Entity, &MemUiData)>)
     }
 }
 
+/// Selects which bot's memory is shown in levels with multiple bots.
+#[derive(Component)]
+pub struct BotTab(usize);
+
+pub fn add_bot_tabs(mut commands: Commands, asset_server: Res<AssetServer>, level: Res<Level>) {
+    if level.bots.len() < 2 {
+        return;
+    }
+
+    // the tabs use at most two rows above the memory, which get
+    // narrower if there are more than 8 bots.
+    let rows = if level.bots.len() > 4 { 2 } else { 1 };
+    let columns = level.bots.len().div_ceil(rows).max(4);
+    let width = 40.0 / columns as f32;
+    for i in 0..level.bots.len() {
+        let (row, column) = (i / columns, i % columns);
+        let label = if columns > 4 {
+            i.to_string()
+        } else {
+            format!("Bot {}", i)
+        };
+        commands
+            .spawn_bundle(ButtonBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(width - 1.0), Val::Px(30.0)),
+                    position_type: PositionType::Absolute,
+                    margin: Rect::all(Val::Auto),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    position: Rect {
+                        left: Val::Auto,
+                        right: Val::Percent(2.0 + (columns - 1 - column) as f32 * width),
+                        top: Val::Percent(7.0 - (rows - 1 - row) as f32 * 5.0),
+                        bottom: Val::Auto,
+                    },
+                    ..Default::default()
+                },
+                color: NORMAL_BUTTON.into(),
+                ..Default::default()
+            })
+            .insert(BotTab(i))
+            .insert(StateLocal)
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        label,
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 25.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+            });
+    }
+}
+
+pub fn update_bot_tabs(
+    state: Res<State<GameState>>,
+    mut editors: ResMut<ProgramEditors>,
+    mem_ui: Res<MemUi>,
+    error: Res<ErrorText>,
+    tabs: Query<(Entity, &BotTab, &Interaction)>,
+    mut color: Query<&mut UiColor>,
+    children: Query<&Children>,
+    mut text: Query<&mut Text>,
+) {
+    for (_, tab, interaction) in tabs.iter() {
+        if matches!(interaction, Interaction::Clicked) && tab.0 != editors.active {
+            if let GameState::Programming = state.current() {
+                // drop the selection in the old tab, as with any other click outside of it.
+                editors.on_selection_quit(None);
+                for &ui in mem_ui.user_names.iter().chain(&mem_ui.user_values) {
+                    *color.get_mut(ui).unwrap() = VALID_MEM.into();
+                }
+                *color.get_mut(error.0).unwrap() = NO_ERROR.into();
+                let text_entity = children.get(error.0).unwrap()[0];
+                text.get_mut(text_entity).unwrap().sections[0].value = String::new();
+            }
+            editors.active = tab.0;
+        }
+    }
+
+    for (entity, tab, interaction) in tabs.iter() {
+        let mut color = color.get_mut(entity).unwrap();
+        *color = if tab.0 == editors.active {
+            SELECTED_MEM.into()
+        } else if let Interaction::Hovered = interaction {
+            HOVERED_BUTTON.into()
+        } else {
+            NORMAL_BUTTON.into()
+        };
+    }
+}
+
 pub trait CornerButton: Sync + Send + 'static {
     const MK: fn(Entity) -> Self;
     const MSG: &'static str;
+    /// Distance from the top of the window in percent.
+    const TOP: f32 = 1.0;
 }
 
 pub fn add_button<T: CornerButton>(mut commands: Commands, asset_server: Res<AssetServer>) {
@@ -249,7 +371,7 @@ pub fn add_button<T: CornerButton>(mut commands: Commands, asset_server: Res<Ass
                 position: Rect {
                     left: Val::Auto,
                     right: Val::Percent(1.0),
-                    top: Val::Percent(1.0),
+                    top: Val::Percent(T::TOP),
                     bottom: Val::Auto,
                 },
                 ..Default::default()
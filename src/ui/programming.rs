@@ -6,8 +6,13 @@ use super::ErrorText;
 use super::MemUi;
 use super::ERROR;
 use super::NO_ERROR;
-use crate::bot::edit::InstructionsEditor;
+use crate::bot::edit::{InstructionsEditor, ProgramEditors};
+use crate::bot::program;
 use crate::bot::BotData;
+use crate::map::Level;
+use crate::replay::{Replay, ReplayPlayback};
+use crate::save::SaveGame;
+use crate::util::StateLocal;
 use crate::GameState;
 use bevy::prelude::*;
 
@@ -17,14 +22,164 @@ impl CornerButton for StartButton {
     const MSG: &'static str = "Start";
 }
 
+pub struct SaveButton(Entity);
+impl CornerButton for SaveButton {
+    const MK: fn(Entity) -> Self = SaveButton;
+    const MSG: &'static str = "Save";
+    const TOP: f32 = 12.0;
+}
+
+pub struct LoadButton(Entity);
+impl CornerButton for LoadButton {
+    const MK: fn(Entity) -> Self = LoadButton;
+    const MSG: &'static str = "Load";
+    const TOP: f32 = 23.0;
+}
+
+pub struct ClearButton(Entity);
+impl CornerButton for ClearButton {
+    const MK: fn(Entity) -> Self = ClearButton;
+    const MSG: &'static str = "Clear";
+    const TOP: f32 = 34.0;
+}
+
+pub struct WatchButton(Entity);
+impl CornerButton for WatchButton {
+    const MK: fn(Entity) -> Self = WatchButton;
+    const MSG: &'static str = "Watch";
+    const TOP: f32 = 45.0;
+}
+
+pub struct HintButton(Entity);
+impl CornerButton for HintButton {
+    const MK: fn(Entity) -> Self = HintButton;
+    const MSG: &'static str = "Hint";
+    const TOP: f32 = 67.0;
+}
+
+/// The title and briefing of the level, followed by the hints revealed so far.
+#[derive(Component)]
+pub struct Briefing;
+
+/// How many hints of the level have been revealed, kept when leaving the programming screen.
+#[derive(Default)]
+pub struct HintsShown {
+    level: String,
+    count: usize,
+}
+
+fn briefing_text(level: &Level, hints: usize) -> String {
+    let mut text = if level.meta.title.is_empty() {
+        level.name.clone()
+    } else {
+        level.meta.title.clone()
+    };
+    if !level.meta.briefing.is_empty() {
+        text += &format!("\n{}", level.meta.briefing);
+    }
+    for (i, hint) in level.meta.hints[..hints].iter().enumerate() {
+        text += &format!("\nhint {}: {}", i + 1, hint);
+    }
+    text
+}
+
+pub fn add_briefing(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    level: Res<Level>,
+    mut hints: ResMut<HintsShown>,
+) {
+    if hints.level != level.name {
+        *hints = HintsShown {
+            level: level.name.clone(),
+            count: 0,
+        };
+    }
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Percent(1.0),
+                    right: Val::Auto,
+                    top: Val::Percent(1.0),
+                    bottom: Val::Auto,
+                },
+                max_size: Size::new(Val::Percent(50.0), Val::Undefined),
+                ..Default::default()
+            },
+            text: Text::with_section(
+                briefing_text(&level, hints.count),
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 20.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(Briefing)
+        .insert(StateLocal);
+}
+
+/// Reveals the next hint of the level.
+pub fn update_hints(
+    mut interaction_query: Query<
+        (&Interaction, &mut UiColor),
+        (Changed<Interaction>, With<Button>),
+    >,
+    button: Res<HintButton>,
+    level: Res<Level>,
+    mut hints: ResMut<HintsShown>,
+    children: Query<&Children>,
+    mut briefing: Query<&mut Text, With<Briefing>>,
+    mut text: Query<&mut Text, Without<Briefing>>,
+) {
+    if let Ok((interaction, mut color)) = interaction_query.get_mut(button.0) {
+        match *interaction {
+            Interaction::Clicked => {
+                if hints.count < level.meta.hints.len() {
+                    hints.count += 1;
+                    for mut briefing in briefing.iter_mut() {
+                        briefing.sections[0].value = briefing_text(&level, hints.count);
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON.into();
+            }
+            Interaction::None => {
+                *color = NORMAL_BUTTON.into();
+            }
+        }
+    }
+
+    let left = level.meta.hints.len() - hints.count;
+    let msg = if left == 0 {
+        String::from("No hints")
+    } else {
+        format!("Hint ({})", left)
+    };
+    let text_entity = children.get(button.0).unwrap()[0];
+    text.get_mut(text_entity).unwrap().sections[0].value = msg;
+}
+
 pub fn update(
+    mut commands: Commands,
     mut interaction_query: Query<(Entity, &Interaction), (Changed<Interaction>, With<Button>)>,
     mut state: ResMut<State<GameState>>,
     input: Res<Input<KeyCode>>,
-    mut mem: ResMut<InstructionsEditor>,
+    mut mem: ResMut<ProgramEditors>,
     mem_ui: Res<MemUi>,
     error_text: Res<ErrorText>,
     start_button: Res<StartButton>,
+    save_button: Res<SaveButton>,
+    load_button: Res<LoadButton>,
+    clear_button: Res<ClearButton>,
+    watch_button: Res<WatchButton>,
+    level: Res<Level>,
     mut color: Query<&mut UiColor>,
     children: Query<&Children>,
     mut text: Query<&mut Text>,
@@ -37,18 +192,46 @@ pub fn update(
         }
     }
 
-    if let Ok((entity, interaction)) = interaction_query.get_mut(start_button.0) {
-        let mut color = color.get_mut(entity).unwrap();
-        match interaction {
-            Interaction::Clicked => {
-                state.set(GameState::Running).unwrap();
-            }
-            Interaction::Hovered => {
-                *color = HOVERED_BUTTON.into();
+    for button in [
+        start_button.0,
+        save_button.0,
+        load_button.0,
+        clear_button.0,
+        watch_button.0,
+    ] {
+        if let Ok((entity, interaction)) = interaction_query.get_mut(button) {
+            let mut color = color.get_mut(entity).unwrap();
+            match interaction {
+                Interaction::Clicked => {}
+                Interaction::Hovered => {
+                    *color = HOVERED_BUTTON.into();
+                }
+                Interaction::None => {
+                    *color = NORMAL_BUTTON.into();
+                }
             }
-            Interaction::None => {
-                *color = NORMAL_BUTTON.into();
+        }
+    }
+
+    if clicked_entity == Some(start_button.0) {
+        let too_large = mem.editors.iter().enumerate().find_map(|(i, editor)| {
+            program::check_memory(&level, &editor.instructions)
+                .err()
+                .map(|e| {
+                    if mem.editors.len() > 1 {
+                        format!("bot {}: {}", i, e)
+                    } else {
+                        e
+                    }
+                })
+        });
+        match too_large {
+            Some(err) => {
+                *color.get_mut(error_text.0).unwrap() = ERROR.into();
+                let text_entity = children.get(error_text.0).unwrap()[0];
+                text.get_mut(text_entity).unwrap().sections[0].value = err;
             }
+            None => state.set(GameState::Running).unwrap(),
         }
     }
 
@@ -64,7 +247,29 @@ pub fn update(
         }
     }
 
+    let ctrl = input.pressed(KeyCode::LControl) || input.pressed(KeyCode::RControl);
+    let mut reverted = false;
+    if ctrl && input.just_pressed(KeyCode::Z) {
+        reverted = mem.undo();
+    } else if ctrl && input.just_pressed(KeyCode::Y) {
+        reverted = mem.redo();
+    }
+
+    if reverted {
+        for &ui in mem_ui.user_names.iter().chain(&mem_ui.user_values) {
+            *color.get_mut(ui).unwrap() = VALID_MEM.into();
+        }
+        *color.get_mut(error_text.0).unwrap() = NO_ERROR.into();
+        let text_entity = children.get(error_text.0).unwrap()[0];
+        text.get_mut(text_entity).unwrap().sections[0].value = String::new();
+    }
+
+    let mut row_edit = None;
     for input in input.get_just_pressed() {
+        if ctrl {
+            break;
+        }
+
         let cell = if let Some(cell) = mem.active_cell_data() {
             cell
         } else {
@@ -134,6 +339,15 @@ pub fn update(
                 update_cell = Some(None);
                 break;
             }
+            KeyCode::Insert | KeyCode::Delete => {
+                if let Some((_, c)) = mem.active_cell {
+                    row_edit = Some((*input == KeyCode::Insert, c));
+                    update_cell = Some(None);
+                }
+                break;
+            }
+            // opens the manual, see `manual::toggle`.
+            KeyCode::F1 => continue,
             key => {
                 eprintln!("unexpected key: {:?}", key);
                 continue;
@@ -142,6 +356,14 @@ pub fn update(
         cell.push(c)
     }
 
+    let save = clicked_entity == Some(save_button.0);
+    let load = clicked_entity == Some(load_button.0);
+    let clear = clicked_entity == Some(clear_button.0);
+    let watch = clicked_entity == Some(watch_button.0);
+    if save || load || clear || watch {
+        update_cell = Some(None);
+    }
+
     if let Some(mut value) = update_cell {
         if value == mem.active_cell {
             value = None;
@@ -151,7 +373,6 @@ pub fn update(
             if mem.error.is_none() {
                 let mut c = color.get_mut(mem_ui.user_names[i]).unwrap();
                 *c = VALID_MEM.into();
-                drop(c);
                 let mut c = color.get_mut(mem_ui.user_values[i]).unwrap();
                 *c = VALID_MEM.into();
             } else {
@@ -193,6 +414,91 @@ pub fn update(
         }
     }
 
+    if clear {
+        mem.replace(InstructionsEditor::new().instructions);
+        for &ui in mem_ui.user_names.iter().chain(&mem_ui.user_values) {
+            *color.get_mut(ui).unwrap() = VALID_MEM.into();
+        }
+        *color.get_mut(error_text.0).unwrap() = NO_ERROR.into();
+        let text_entity = children.get(error_text.0).unwrap()[0];
+        text.get_mut(text_entity).unwrap().sections[0].value = String::new();
+    }
+
+    if let Some((insert, row)) = row_edit {
+        if mem.error.is_none() {
+            let warning = if insert {
+                mem.insert_row(row)
+            } else {
+                mem.delete_row(row)
+            };
+            for &ui in mem_ui.user_names.iter().chain(&mem_ui.user_values) {
+                *color.get_mut(ui).unwrap() = VALID_MEM.into();
+            }
+
+            let mut error_color = color.get_mut(error_text.0).unwrap();
+            let text_entity = children.get(error_text.0).unwrap()[0];
+            let text = &mut text.get_mut(text_entity).unwrap().sections[0].value;
+            if let Some(warning) = warning {
+                *error_color = ERROR.into();
+                *text = warning;
+            } else {
+                *error_color = NO_ERROR.into();
+                *text = String::new();
+            }
+        }
+    }
+
+    if (save || load) && mem.error.is_none() {
+        let path = program::level_path(&level, mem.active);
+        let status = if save {
+            program::save(&path, &mem.instructions)
+                .map(|()| format!("saved the program to {:?}", path))
+        } else {
+            program::load(&path).map(|instructions| {
+                mem.replace(instructions);
+                for &ui in mem_ui.user_names.iter().chain(&mem_ui.user_values) {
+                    *color.get_mut(ui).unwrap() = VALID_MEM.into();
+                }
+                format!("loaded the program from {:?}", path)
+            })
+        };
+
+        let mut error_color = color.get_mut(error_text.0).unwrap();
+        let text_entity = children.get(error_text.0).unwrap()[0];
+        let text = &mut text.get_mut(text_entity).unwrap().sections[0].value;
+        match status {
+            Ok(msg) => {
+                *error_color = NO_ERROR.into();
+                *text = msg;
+            }
+            Err(err) => {
+                *error_color = ERROR.into();
+                *text = err;
+            }
+        }
+    }
+
+    if watch {
+        let replay = Replay::latest(&level).and_then(|path| {
+            let replay = Replay::load(&path)?;
+            replay
+                .verify(&level)
+                .map(|()| replay)
+                .map_err(|e| format!("cannot watch {:?}, {}", path, e))
+        });
+        match replay {
+            Ok(replay) => {
+                commands.insert_resource(ReplayPlayback { replay, tick: 0 });
+                state.set(GameState::Replay).unwrap();
+            }
+            Err(err) => {
+                *color.get_mut(error_text.0).unwrap() = ERROR.into();
+                let text_entity = children.get(error_text.0).unwrap()[0];
+                text.get_mut(text_entity).unwrap().sections[0].value = err;
+            }
+        }
+    }
+
     for (data, &ui) in iter::zip(&mem.user_names, &mem_ui.user_names) {
         let text_entity = children.get(ui).unwrap()[0];
         text.get_mut(text_entity).unwrap().sections[0].value = data.clone();
@@ -203,10 +509,40 @@ pub fn update(
     }
 }
 
-pub fn exit(mut instructions: ResMut<InstructionsEditor>, mut bot_data: Query<&mut BotData>) {
-    // TODO: this is wrong, only one bot. move to update.
-    instructions.on_selection_quit(None);
+/// Stores the programs in the save game after each edit,
+/// so they aren't lost when the window is closed while programming.
+pub fn store_programs(editors: Res<ProgramEditors>, level: Res<Level>, mut save: ResMut<SaveGame>) {
+    if !editors.is_changed() {
+        return;
+    }
+
+    let programs = editors
+        .editors
+        .iter()
+        .map(|e| e.instructions)
+        .collect::<Vec<_>>();
+    if save
+        .levels
+        .get(&level.name)
+        .map_or(false, |l| l.programs == programs)
+    {
+        return;
+    }
+    save.level(&level.name).programs = programs;
+    save.store();
+}
+
+pub fn exit(
+    mut editors: ResMut<ProgramEditors>,
+    level: Res<Level>,
+    mut save: ResMut<SaveGame>,
+    mut bot_data: Query<&mut BotData>,
+) {
+    editors.on_selection_quit(None);
     for mut bot_data in bot_data.iter_mut() {
-        bot_data.instructions = instructions.instructions;
+        bot_data.instructions = editors.editors[bot_data.index].instructions;
     }
+
+    save.level(&level.name).programs = editors.editors.iter().map(|e| e.instructions).collect();
+    save.store();
 }
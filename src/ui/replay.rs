@@ -0,0 +1,68 @@
+use super::buttons::*;
+use super::CornerButton;
+use super::ErrorText;
+use super::MemUi;
+use super::NO_ERROR;
+use crate::bot::edit::ProgramEditors;
+use crate::bot::Instruction;
+use crate::replay::ReplayPlayback;
+use crate::GameState;
+use bevy::prelude::*;
+use std::iter;
+
+pub struct StopReplayButton(Entity);
+impl CornerButton for StopReplayButton {
+    const MK: fn(Entity) -> Self = StopReplayButton;
+    const MSG: &'static str = "Stop";
+}
+
+/// Shows the recorded program of the active bot.
+pub fn init(
+    mem_ui: Res<MemUi>,
+    editors: Res<ProgramEditors>,
+    playback: Res<ReplayPlayback>,
+    children: Query<&Children>,
+    mut text: Query<&mut Text>,
+) {
+    let instructions = &playback.replay.programs[editors.active];
+    let iter = iter::zip(
+        instructions,
+        iter::zip(&mem_ui.user_names, &mem_ui.user_values),
+    );
+    for (&instr, (&name, &value)) in iter {
+        let text_entity = children.get(name).unwrap()[0];
+        text.get_mut(text_entity).unwrap().sections[0].value =
+            Instruction::from_repr(instr).map_or(String::new(), |i| i.to_string());
+
+        let text_entity = children.get(value).unwrap()[0];
+        text.get_mut(text_entity).unwrap().sections[0].value = instr.to_string();
+    }
+}
+
+pub fn update(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<Button>)>,
+    mut state: ResMut<State<GameState>>,
+    stop: Res<StopReplayButton>,
+    playback: Res<ReplayPlayback>,
+    error_text: Res<ErrorText>,
+    mut color: Query<&mut UiColor>,
+    children: Query<&Children>,
+    mut text: Query<&mut Text>,
+) {
+    if let Ok(interaction) = interaction_query.get(stop.0) {
+        let mut color = color.get_mut(stop.0).unwrap();
+        match *interaction {
+            Interaction::Clicked => state.set(GameState::Programming).unwrap(),
+            Interaction::Hovered => *color = HOVERED_BUTTON.into(),
+            Interaction::None => *color = NORMAL_BUTTON.into(),
+        }
+    }
+
+    *color.get_mut(error_text.0).unwrap() = NO_ERROR.into();
+    let text_entity = children.get(error_text.0).unwrap()[0];
+    text.get_mut(text_entity).unwrap().sections[0].value = format!(
+        "watching the replay, tick {} / {}",
+        playback.tick,
+        playback.replay.ticks.len()
+    );
+}
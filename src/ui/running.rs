@@ -3,11 +3,19 @@ use super::CornerButton;
 use super::ErrorText;
 use super::MemUi;
 use super::ERROR;
+use crate::bot::debugger::Debugger;
+use crate::bot::edit::ProgramEditors;
+use crate::bot::sim::Simulation;
+use crate::bot::timeline::Timeline;
 use crate::bot::BotData;
-use crate::bot::BotState;
 use crate::bot::ExecutionFailure;
 use crate::bot::Instruction;
-use crate::GameState;
+use crate::bot::SimEntities;
+use crate::draw::{self, DrawUpdates, SimSpeed};
+use crate::map::{GridPos, Level, LevelList};
+use crate::replay::{Replay, ReplayKind};
+use crate::util::StateLocal;
+use crate::{CurrentLevel, GameState};
 use bevy::prelude::*;
 use std::iter;
 
@@ -17,13 +25,66 @@ impl CornerButton for StopButton {
     const MSG: &'static str = "Stop";
 }
 
+pub struct PauseButton(Entity);
+impl CornerButton for PauseButton {
+    const MK: fn(Entity) -> Self = PauseButton;
+    const MSG: &'static str = "Pause";
+    const TOP: f32 = 12.0;
+}
+
+pub struct TickButton(Entity);
+impl CornerButton for TickButton {
+    const MK: fn(Entity) -> Self = TickButton;
+    const MSG: &'static str = "Tick";
+    const TOP: f32 = 23.0;
+}
+
+pub struct NextButton(Entity);
+impl CornerButton for NextButton {
+    const MK: fn(Entity) -> Self = NextButton;
+    const MSG: &'static str = "Next";
+    const TOP: f32 = 34.0;
+}
+
+pub struct SlowerButton(Entity);
+impl CornerButton for SlowerButton {
+    const MK: fn(Entity) -> Self = SlowerButton;
+    const MSG: &'static str = "Slower";
+    const TOP: f32 = 45.0;
+}
+
+pub struct FasterButton(Entity);
+impl CornerButton for FasterButton {
+    const MK: fn(Entity) -> Self = FasterButton;
+    const MSG: &'static str = "Faster";
+    const TOP: f32 = 56.0;
+}
+
+pub struct SaveReplayButton(Entity);
+impl CornerButton for SaveReplayButton {
+    const MK: fn(Entity) -> Self = SaveReplayButton;
+    const MSG: &'static str = "Save";
+    const TOP: f32 = 67.0;
+}
+
+/// Offered after beating the level if there is another one to play.
+pub struct NextLevelButton(Entity);
+impl CornerButton for NextLevelButton {
+    const MK: fn(Entity) -> Self = NextLevelButton;
+    const MSG: &'static str = "Next level";
+    const TOP: f32 = 78.0;
+}
+
 pub fn init(
     mem_ui: Res<MemUi>,
+    editors: Res<ProgramEditors>,
+    mut debugger: ResMut<Debugger>,
     mem: Query<&BotData>,
     children: Query<&Children>,
     mut text: Query<&mut Text>,
 ) {
-    for mem in mem.iter() {
+    debugger.restart();
+    for mem in mem.iter().filter(|mem| mem.index == editors.active) {
         let iter = iter::zip(
             &mem.instructions,
             iter::zip(&mem_ui.user_names, &mem_ui.user_values),
@@ -45,12 +106,135 @@ pub fn update1(
         (Changed<Interaction>, With<Button>),
     >,
     mut state: ResMut<State<GameState>>,
+    input: Res<Input<KeyCode>>,
+    mut debugger: ResMut<Debugger>,
+    mut speed: ResMut<SimSpeed>,
+    editors: Res<ProgramEditors>,
+    mem_ui: Res<MemUi>,
     stop: Res<StopButton>,
+    pause: Res<PauseButton>,
+    tick: Res<TickButton>,
+    next: Res<NextButton>,
+    slower: Res<SlowerButton>,
+    faster: Res<FasterButton>,
+    children: Query<&Children>,
+    mut text: Query<&mut Text>,
+) {
+    for button in [stop.0, pause.0, tick.0, next.0, slower.0, faster.0] {
+        if let Ok((interaction, mut color)) = interaction_query.get_mut(button) {
+            match *interaction {
+                Interaction::Clicked => {
+                    if button == stop.0 {
+                        state.set(GameState::Programming).unwrap();
+                    } else if button == pause.0 {
+                        debugger.toggle_pause();
+                    } else if button == tick.0 {
+                        debugger.step_tick();
+                    } else if button == next.0 {
+                        debugger.step_instruction(editors.active);
+                    } else if button == slower.0 {
+                        speed.slower();
+                    } else {
+                        speed.faster();
+                    }
+                }
+                Interaction::Hovered => {
+                    *color = HOVERED_BUTTON.into();
+                }
+                Interaction::None => {
+                    *color = NORMAL_BUTTON.into();
+                }
+            }
+        }
+    }
+
+    if input.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        speed.slower();
+    }
+    if input.any_just_pressed([KeyCode::Plus, KeyCode::Equals, KeyCode::NumpadAdd]) {
+        speed.faster();
+    }
+
+    for (cell, &ui) in mem_ui.user_names.iter().enumerate() {
+        if let Ok((Interaction::Clicked, _)) = interaction_query.get(ui) {
+            debugger.toggle_breakpoint(editors.active, cell);
+        }
+    }
+
+    let text_entity = children.get(pause.0).unwrap()[0];
+    let msg = if debugger.paused { "Resume" } else { "Pause" };
+    text.get_mut(text_entity).unwrap().sections[0].value = msg.to_string();
+}
+
+/// Saves the run up to the current tick as a replay of the level.
+pub fn save_replay(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<Button>)>,
+    button: Res<SaveReplayButton>,
+    sim: Res<Simulation>,
+    timeline: Res<Timeline>,
+    level: Res<Level>,
+    error: Res<ErrorText>,
+    mut color: Query<&mut UiColor>,
+    children: Query<&Children>,
+    mut text: Query<&mut Text>,
+) {
+    let interaction = if let Ok(interaction) = interaction_query.get(button.0) {
+        interaction
+    } else {
+        return;
+    };
+
+    let msg = match *interaction {
+        Interaction::Clicked => {
+            let programs = sim.bots.iter().map(|b| b.instructions).collect::<Vec<_>>();
+            let steps = timeline.steps(sim.tick).to_vec();
+            let replay = Replay::new(&level, &programs, steps);
+            let path = Replay::path(&level, ReplayKind::Saved);
+            match path.and_then(|path| replay.save(&path)) {
+                Ok(()) => "Saved",
+                Err(e) => {
+                    error.show(e, &mut color, &children, &mut text);
+                    "Failed"
+                }
+            }
+        }
+        Interaction::Hovered => {
+            *color.get_mut(button.0).unwrap() = HOVERED_BUTTON.into();
+            SaveReplayButton::MSG
+        }
+        Interaction::None => {
+            *color.get_mut(button.0).unwrap() = NORMAL_BUTTON.into();
+            SaveReplayButton::MSG
+        }
+    };
+    let text_entity = children.get(button.0).unwrap()[0];
+    text.get_mut(text_entity).unwrap().sections[0].value = msg.to_string();
+}
+
+/// Continues with the level chosen by `LevelList::next_level`.
+pub fn next_level(
+    mut interaction_query: Query<
+        (&Interaction, &mut UiColor),
+        (Changed<Interaction>, With<Button>),
+    >,
+    button: Option<Res<NextLevelButton>>,
+    levels: Res<LevelList>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut state: ResMut<State<GameState>>,
 ) {
-    if let Ok((interaction, mut color)) = interaction_query.get_mut(stop.0) {
+    let button = if let Some(button) = button {
+        button
+    } else {
+        return;
+    };
+
+    if let Ok((interaction, mut color)) = interaction_query.get_mut(button.0) {
         match *interaction {
             Interaction::Clicked => {
-                state.set(GameState::Programming).unwrap();
+                if let Some(next) = levels.next_level(current_level.0) {
+                    current_level.0 = next;
+                    state.set(GameState::ChangeLevel).unwrap();
+                }
             }
             Interaction::Hovered => {
                 *color = HOVERED_BUTTON.into();
@@ -62,29 +246,176 @@ pub fn update1(
     }
 }
 
+#[derive(Component)]
+pub struct TimelineBar;
+
+#[derive(Component)]
+pub struct TimelineFill;
+
+#[derive(Component)]
+pub struct TimelineLabel;
+
+pub fn add_timeline(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Auto, Val::Px(30.0)),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Percent(1.0),
+                    right: Val::Percent(60.0),
+                    top: Val::Percent(1.0),
+                    bottom: Val::Auto,
+                },
+                ..Default::default()
+            },
+            color: NORMAL_BUTTON.into(),
+            ..Default::default()
+        })
+        .insert(TimelineBar)
+        .insert(StateLocal)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+                        position_type: PositionType::Absolute,
+                        position: Rect {
+                            left: Val::Px(0.0),
+                            top: Val::Px(0.0),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    color: SELECTED_MEM.into(),
+                    ..Default::default()
+                })
+                .insert(TimelineFill);
+            parent
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Auto),
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 25.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(TimelineLabel);
+        });
+}
+
+/// Shows the current tick on the timeline and rewinds
+/// the simulation to the tick selected by dragging it.
+pub fn update_timeline(
+    windows: Res<Windows>,
+    mouse: Res<Input<MouseButton>>,
+    bar: Query<(&Interaction, &Node, &GlobalTransform), With<TimelineBar>>,
+    mut fill: Query<&mut Style, With<TimelineFill>>,
+    mut label: Query<&mut Text, With<TimelineLabel>>,
+    timeline: Res<Timeline>,
+    speed: Res<SimSpeed>,
+    level: Res<Level>,
+    entities: Res<SimEntities>,
+    mut sim: ResMut<Simulation>,
+    mut debugger: ResMut<Debugger>,
+    mut render_steps: ResMut<DrawUpdates>,
+    mut positions: Query<(&mut GridPos, &mut Transform)>,
+) {
+    let last_tick = timeline.last_tick();
+    let cursor = windows.get_primary().and_then(|w| w.cursor_position());
+    for (interaction, node, transform) in bar.iter() {
+        if let (Interaction::Clicked, true, Some(cursor)) =
+            (interaction, mouse.pressed(MouseButton::Left), cursor)
+        {
+            let left = transform.translation.x - node.size.x / 2.0;
+            let fraction = ((cursor.x - left) / node.size.x).clamp(0.0, 1.0);
+            let tick = (fraction * last_tick as f32).round() as u64;
+            if tick != sim.tick {
+                let snapshot = timeline.get(tick).unwrap();
+                sim.restore(snapshot);
+                debugger.paused = true;
+                debugger.advance = None;
+                render_steps.data.clear();
+
+                let bots = snapshot.bots.iter().map(|b| (b.pos, Some(b.state.dir)));
+                let boxes = snapshot.boxes.iter().map(|b| (b.pos, None));
+                let entities = entities.bots().iter().chain(entities.boxes());
+                for (&entity, (pos, dir)) in entities.zip(bots.chain(boxes)) {
+                    let (mut grid_pos, mut transform) = positions.get_mut(entity).unwrap();
+                    *grid_pos = pos;
+                    draw::place_entity(&level.map, pos, dir, &mut transform);
+                }
+            }
+        }
+    }
+
+    let fraction = if last_tick == 0 {
+        0.0
+    } else {
+        sim.tick as f32 / last_tick as f32
+    };
+    for mut style in fill.iter_mut() {
+        style.size.width = Val::Percent(fraction * 100.0);
+    }
+    for mut text in label.iter_mut() {
+        text.sections[0].value = format!("tick {} / {} at {}", sim.tick, last_tick, *speed);
+    }
+}
+
 pub fn update2(
     mem_ui: Res<MemUi>,
     error: Res<ErrorText>,
     error_msg: Option<Res<ExecutionFailure>>,
-    bots: Query<(&BotData, &BotState)>,
+    sim: Res<Simulation>,
+    editors: Res<ProgramEditors>,
+    debugger: Res<Debugger>,
     children: Query<&Children>,
     mut text: Query<&mut Text>,
     mut color_query: Query<&mut UiColor>,
 ) {
-    for (data, state) in bots.iter() {
-        let instr = state.prev_instruction;
-        let mut color = color_query
-            .get_mut(mem_ui.user_names[instr as usize])
-            .unwrap();
-        *color = SELECTED_MEM.into();
-
-        if Instruction::from_repr(data.instructions[state.prev_instruction as usize])
-            .map_or(false, |i| i.is_wide())
+    for (cell, &ui) in mem_ui.user_names.iter().enumerate() {
+        if debugger.is_breakpoint(editors.active, cell) {
+            *color_query.get_mut(ui).unwrap() = BREAKPOINT.into();
+        }
+    }
+
+    if let Some(bot) = sim.bots.get(editors.active) {
+        // show the memory as changed by the program so far.
+        let cells = iter::zip(&mem_ui.user_names, &mem_ui.user_values);
+        for ((&name, &value), (&live, &program)) in
+            cells.zip(iter::zip(&bot.state.memory, &bot.instructions))
         {
-            let mut color = color_query
-                .get_mut(mem_ui.user_values[(instr as usize + 1) % 32])
-                .unwrap();
-            *color = SELECTED_MEM.into();
+            let text_entity = children.get(name).unwrap()[0];
+            text.get_mut(text_entity).unwrap().sections[0].value =
+                Instruction::from_repr(live).map_or(String::new(), |i| i.to_string());
+            let text_entity = children.get(value).unwrap()[0];
+            text.get_mut(text_entity).unwrap().sections[0].value = live.to_string();
+            if live != program {
+                *color_query.get_mut(value).unwrap() = CHANGED_MEM.into();
+            }
+        }
+
+        // nothing is highlighted after jumping outside of the memory.
+        let instr = bot.state.prev_instruction as usize;
+        if let Some(&ui) = mem_ui.user_names.get(instr) {
+            *color_query.get_mut(ui).unwrap() = SELECTED_MEM.into();
+
+            let operands =
+                Instruction::from_repr(bot.state.memory[instr]).map_or(0, |i| i.operands().len());
+            for i in 1..=operands {
+                let mut color = color_query
+                    .get_mut(mem_ui.user_values[(instr + i) % 32])
+                    .unwrap();
+                *color = SELECTED_MEM.into();
+            }
         }
     }
 
@@ -1,8 +1,9 @@
 use bevy::prelude::*;
 
 use crate::{
-    bot::{self, edit::InstructionsEditor, BotData, BotState, VoidedOrExited},
+    bot::{self, debugger::Debugger, edit::ProgramEditors, BotData},
     map::{self, BoxData, EntityKind, Level, LevelList},
+    save::SaveGame,
     CurrentLevel, GameState,
 };
 
@@ -33,19 +34,29 @@ pub fn spawn_map_entities(
     mut commands: Commands,
     level: Res<Level>,
     mut state: ResMut<State<GameState>>,
-    mut instructions_editor: ResMut<InstructionsEditor>,
+    mut editors: ResMut<ProgramEditors>,
+    mut debugger: ResMut<Debugger>,
+    save: Res<SaveGame>,
     queryyy: Query<Entity, With<EntityKind>>,
 ) {
-    *instructions_editor = InstructionsEditor::new();
+    let programs = save
+        .levels
+        .get(&level.name)
+        .map_or(&[][..], |level| &level.programs);
+    *editors = ProgramEditors::from_programs(level.bots.len(), programs);
+    *debugger = Debugger::new(level.bots.len());
 
     for e in queryyy.iter() {
         commands.entity(e).despawn();
     }
 
-    for &(bot_pos, dir) in &level.bots {
+    for (i, &(bot_pos, dir)) in level.bots.iter().enumerate() {
         commands
             .spawn()
-            .insert(bot::BotData::new(bot_pos, dir))
+            .insert(bot::BotData {
+                instructions: editors.editors[i].instructions,
+                ..bot::BotData::new(i, bot_pos, dir)
+            })
             .insert(map::EntityKind::Robot);
     }
     for &box_pos in &level.boxes {
@@ -69,10 +80,6 @@ pub fn reset_bot_and_box_state(world: &mut World) {
         with_pos.push((entity, data.start_position));
     }
     for (entity, data) in with_pos {
-        world
-            .entity_mut(entity)
-            .insert(data)
-            .remove::<VoidedOrExited>();
-        world.entity_mut(entity).remove::<BotState>();
+        world.entity_mut(entity).insert(data);
     }
 }